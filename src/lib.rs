@@ -1,20 +1,20 @@
 //! Author: Steven Frederiksen
 mod tracing;
+use std::path::Path;
 use std::time::Instant;
 use std::{
     io::Write,
     sync::{mpsc, Arc},
 };
 
-use image::error::ImageResult;
-use image::RgbImage;
+use image::error::{ImageError, ImageResult, ParameterError, ParameterErrorKind};
 use rand::Rng;
 use threadpool::ThreadPool;
 use tracing::materials::{Dielectric, Lambertian, Metal};
 pub use tracing::*;
 
 pub fn random_scene() -> HittableMap {
-    let mut rng = rand::thread_rng();
+    let mut rng = rng();
     let mut world = HittableMap::new();
 
     let ground_mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
@@ -68,7 +68,7 @@ pub fn random_scene() -> HittableMap {
     world
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 pub struct SceneConfig {
     pub aspect_ratio: f64,
     pub image_width: u32,
@@ -81,10 +81,34 @@ pub struct SceneConfig {
     pub vfov: f64,
     pub dist_to_focus: f64,
     pub aperature: f64,
+    /// Replaces `dist_to_focus` for every frame unless it's `Focus::Fixed`.
+    pub focus: Focus,
+    pub projection: Projection,
+    /// Shape of the lens opening, only used by the perspective camera.
+    pub bokeh: Bokeh,
+    /// Lens shift and tilt, only used by the perspective camera.
+    pub tilt_shift: TiltShift,
+    /// Real lens elements the perspective camera traces through instead of
+    /// its thin lens.
+    pub lens: Option<LensSystem>,
+    /// Renders both eyes side by side or over under when set.
+    pub stereo: Option<Stereo>,
+    /// Photographic settings, `vfov` and `aperature` are derived from them
+    /// when the config is loaded and the exposure when tone mapping.
+    pub physical: Option<PhysicalCamera>,
     pub num_threads: usize,
+    pub tone_mapping: ToneMapping,
+    pub filter: PixelFilter,
+    pub aov_output: AovOutput,
+    pub denoise: Option<DenoiseSettings>,
+    /// Seed for the sampler, `None` draws fresh entropy for every render.
+    pub seed: Option<u64>,
+    /// Renders only part of the image when set.
+    pub region: Option<RenderRegion>,
 }
 
 impl SceneConfig {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         aspect_ratio: f64,
         image_width: u32,
@@ -111,11 +135,69 @@ impl SceneConfig {
             vfov,
             dist_to_focus,
             aperature,
+            focus: Focus::default(),
+            projection: Projection::default(),
+            bokeh: Bokeh::default(),
+            tilt_shift: TiltShift::default(),
+            lens: None,
+            stereo: None,
+            physical: None,
             num_threads,
+            tone_mapping: ToneMapping::default(),
+            filter: PixelFilter::default(),
+            aov_output: AovOutput::default(),
+            denoise: None,
+            seed: None,
+            region: None,
+        }
+    }
+
+    /// The config with `dist_to_focus` set by `focus` for `world`, it's kept
+    /// when there's nothing to focus on.
+    pub fn focused(&self, world: &HittableMap) -> SceneConfig {
+        SceneConfig {
+            dist_to_focus: self
+                .focus
+                .distance(world, self)
+                .unwrap_or(self.dist_to_focus),
+            ..self.clone()
+        }
+    }
+
+    /// `tone_mapping` with the exposure of the physical camera added to its
+    /// own, which then acts as exposure compensation.
+    pub fn display_tone_mapping(&self) -> ToneMapping {
+        match self.physical {
+            Some(physical) => ToneMapping {
+                exposure: self.tone_mapping.exposure + physical.exposure(),
+                ..self.tone_mapping
+            },
+            None => self.tone_mapping,
         }
     }
 }
 
+impl Default for SceneConfig {
+    fn default() -> Self {
+        let aspect_ratio = 16.0 / 9.0;
+        let image_width = 300;
+        SceneConfig::new(
+            aspect_ratio,
+            image_width,
+            (image_width as f64 / aspect_ratio) as u32,
+            30,
+            20,
+            Point3::new(13.0, 2.0, 3.0),
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            20.0,
+            10.0,
+            0.1,
+            16,
+        )
+    }
+}
+
 pub fn benchmark_scene(
     output_file: &str,
     world: Arc<impl Hittable + 'static>,
@@ -140,34 +222,127 @@ pub fn render_scene(
     world: Arc<impl Hittable + 'static>,
     scene_config: Arc<SceneConfig>,
 ) -> ImageResult<()> {
-    let camera = Camera::from(&scene_config);
-    let camera_arc = Arc::new(camera);
+    let output = render_passes(world, Arc::clone(&scene_config))?;
+    let output_path = Path::new(output_file);
+    let tone_mapping = scene_config.display_tone_mapping();
+    let beauty = if let Some(settings) = scene_config.denoise {
+        if settings.keep_raw {
+            let raw_file = match output_path.extension() {
+                Some(ext) => sibling_path(output_path, &format!("_raw.{}", ext.to_string_lossy())),
+                None => sibling_path(output_path, "_raw"),
+            };
+            output.beauty.save(raw_file, &tone_mapping)?;
+        }
+        denoise(
+            &output.beauty,
+            &output.aovs,
+            &settings,
+            scene_config.num_threads,
+        )
+    } else {
+        output.beauty
+    };
+    match &scene_config.region {
+        Some(
+            region @ RenderRegion {
+                crop: false,
+                background: Some(background),
+                ..
+            },
+        ) => beauty.save_over(output_path, &tone_mapping, background, region)?,
+        _ => beauty.save(output_path, &tone_mapping)?,
+    }
+    output
+        .aovs
+        .save(scene_config.aov_output, output_path, &beauty)
+}
+
+pub struct RenderOutput {
+    pub beauty: FrameBuffer,
+    pub aovs: AovBuffers,
+}
+
+pub fn render_framebuffer(
+    world: Arc<impl Hittable + 'static>,
+    scene_config: Arc<SceneConfig>,
+) -> ImageResult<FrameBuffer> {
+    Ok(render_passes(world, scene_config)?.beauty)
+}
+
+pub fn render_passes(
+    world: Arc<impl Hittable + 'static>,
+    scene_config: Arc<SceneConfig>,
+) -> ImageResult<RenderOutput> {
+    if let Some(region) = &scene_config.region {
+        if !region.fits(scene_config.image_width, scene_config.image_height) {
+            return Err(ImageError::Parameter(ParameterError::from_kind(
+                ParameterErrorKind::Generic(format!(
+                    "region {}x{} at {},{} isn't inside the {}x{} image",
+                    region.width,
+                    region.height,
+                    region.x,
+                    region.y,
+                    scene_config.image_width,
+                    scene_config.image_height
+                )),
+            )));
+        }
+    }
+    let camera_arc = build_camera(&scene_config);
     // Render
     // -- TP
     let threadpool = ThreadPool::new(scene_config.num_threads);
-    let mut img = RgbImage::new(scene_config.image_width, scene_config.image_height);
+    let mut film = Film::new(scene_config.image_width, scene_config.image_height);
+    let mut aovs = AovBuffers::new(scene_config.image_width, scene_config.image_height);
+
+    // the whole image unless a region is set, rows counted from the top
+    let (columns, rows) = match &scene_config.region {
+        Some(region) => (region.columns(), region.rows()),
+        None => (0..scene_config.image_width, 0..scene_config.image_height),
+    };
 
     let (res_tx, res_rx) = mpsc::channel();
-    for j in (0..scene_config.image_height).rev() {
+    for row in rows.clone() {
         let res_tx = res_tx.clone();
         let world_arc = Arc::clone(&world);
         let camera_arc = Arc::clone(&camera_arc);
         let scene_config = Arc::clone(&scene_config);
+        let columns = columns.clone();
         threadpool.execute(move || {
-            for i in 0..scene_config.image_width {
-                let mut color = Color::empty();
+            let width = scene_config.image_width;
+            let height = scene_config.image_height;
+            let filter = scene_config.filter;
+            let j = height - 1 - row;
+            if let Some(seed) = scene_config.seed {
+                seed_row(seed, row);
+            }
+            let mut rng = rng();
+            let mut tile = FilmTile::new(
+                columns.clone(),
+                row..row + 1,
+                width,
+                height,
+                filter.radius(),
+            );
+            let mut row_aovs = Vec::with_capacity(columns.len());
+            for i in columns {
+                let mut aov_pixel = AovPixel::new();
                 for _ in 0..scene_config.samples_per_pixel {
-                    let u =
-                        (i as f64 + rand::random::<f64>()) / (scene_config.image_width - 1) as f64;
-                    let v =
-                        (j as f64 + rand::random::<f64>()) / (scene_config.image_height - 1) as f64;
-                    let ray = camera_arc.get_ray(u, v);
-                    color += ray.ray_color(&world_arc, scene_config.max_depth);
+                    let s = i as f64 + rng.gen::<f64>();
+                    let t = j as f64 + rng.gen::<f64>();
+                    let u = s / (width - 1) as f64;
+                    let v = t / (height - 1) as f64;
+                    let (color, aov) = match camera_arc.get_ray(u, v) {
+                        Some(ray) => ray.ray_color_aov(&world_arc, scene_config.max_depth),
+                        None => (Color::empty(), AovSample::miss(Color::empty())),
+                    };
+                    // image rows run top to bottom, the camera's v runs bottom to top
+                    tile.add_sample(s, height as f64 - t, color, &filter);
+                    aov_pixel.add(&aov, color);
                 }
-                res_tx
-                    .send((i, scene_config.image_height - 1 - j, color))
-                    .expect("unable to send");
+                row_aovs.push(aov_pixel.resolve());
             }
+            res_tx.send((row, tile, row_aovs)).expect("unable to send");
         });
     }
     drop(res_tx);
@@ -176,20 +351,33 @@ pub fn render_scene(
     let mut stdout = std::io::stdout();
     let now = Instant::now();
 
-    for (x, y, color) in res_rx {
-        pixels_done += 1;
-        img.put_pixel(x, y, color.to_rgb_aa(scene_config.samples_per_pixel));
+    for (row, tile, row_aovs) in res_rx {
+        pixels_done += columns.len();
+        film.merge(&tile);
+        for (x, aov) in columns.clone().zip(row_aovs.iter()) {
+            aovs.put_pixel(x, row, aov);
+        }
         print!(
             "{}[1K\rPixels Done: {}/{}",
             27 as char, // this resets the line
             pixels_done,
-            scene_config.image_height * scene_config.image_width
+            columns.len() * rows.len()
         );
         stdout.flush()?;
     }
-    img.save(output_file)?;
     println!("\nDone. Took {:.2?}", now.elapsed());
-    Ok(())
+    let mut beauty = film.to_framebuffer();
+    if let Some(region) = &scene_config.region {
+        if region.crop {
+            return Ok(RenderOutput {
+                beauty: beauty.crop(region),
+                aovs: aovs.crop(region),
+            });
+        }
+        // the filter spreads samples a little past the region
+        beauty.clear_outside(region);
+    }
+    Ok(RenderOutput { beauty, aovs })
 }
 
 pub fn animate_scene(output_file_base: &str, animation: Animation) -> ImageResult<()> {
@@ -211,6 +399,8 @@ impl Iterator for Animation {
 
     fn next(&mut self) -> Option<Self::Item> {
         if let (Some(world), Some(cfg)) = (self.m_world.next(), self.m_config.next()) {
+            // focus again, the camera or its target may have moved
+            let cfg = Arc::new(cfg.focused(&world));
             Some((world, cfg))
         } else {
             None
@@ -290,11 +480,9 @@ impl WorldTransformer for ObjectMover {
             false
         } else {
             self.t += 1;
-            if let Some(object) = world.objects.get_mut(&self.name) {
-                object.set_origin(object.origin() + self.step);
-                true
-            } else {
-                false
+            match world.origin(&self.name) {
+                Some(origin) => world.set_origin(&self.name, origin + self.step),
+                None => false,
             }
         }
     }
@@ -1,75 +1,436 @@
 //! Author: Steven Frederiksen
-use std::sync::Arc;
+use std::{fs, path::PathBuf, process::ExitCode, sync::Arc};
 
-use ray_tracing::{animate_scene, benchmark_scene, random_scene};
+use clap::{Args, Parser, Subcommand};
+use image::ImageError;
 use ray_tracing::{
-    AnimatedConfig, AnimatedWorld, Animation, CameraTransformer, ObjectMover, Point3, SceneConfig,
-    Vec3,
+    animate_scene, benchmark_scene, load_camera_animation, load_gltf, load_pbrt, load_scene,
+    random_scene, render_scene, save_scene, seed_rng, trace_path, AnimatedConfig, AnimatedWorld,
+    Animation, CameraAnimation, CameraTransformer, HittableMap, KeyframedCamera, ObjectMover,
+    Point3, SceneConfig, SceneTransformer, WorldTransformer,
 };
 
 /*
  * Ray Tracting Example
  */
 
-fn main() {
-    // Image
-    const ASPECT_RATIO: f64 = 16.0 / 9.0;
-    let image_width: u32 = 300;
-    let image_height = (image_width as f64 / ASPECT_RATIO) as u32;
-    let samples_per_pixel = 30;
-    let max_depth = 20;
-
-    // World
-    let world = random_scene();
-    let world_arc = Arc::new(world);
-
-    // Camera
-    let lookfrom = Point3::new(13.0, 2.0, 3.0);
-    let lookat = Point3::new(0.0, 0.0, 0.0);
-    let vup = Vec3::new(0.0, 1.0, 0.0);
-    let vfov = 20.0;
-    let dist_to_focus = 10.0;
-    let aperature = 0.1;
-    let num_threads = 16;
-    let scene_config = SceneConfig::new(
-        ASPECT_RATIO,
-        image_width,
-        image_height,
-        samples_per_pixel,
-        max_depth,
-        lookfrom,
-        lookat,
-        vup,
-        vfov,
-        dist_to_focus,
-        aperature,
-        num_threads,
-    );
-    let scene_config_arc = Arc::new(scene_config);
-
-    // Animation
-    let main1_start = Point3::new(0.0, 1.0, 0.0);
-    let main1_stop = Point3::new(0.0, 3.0, 0.0);
-    let main1_mover = ObjectMover::new("main1".to_string(), main1_start, main1_stop, 240);
-    let world_animation = AnimatedWorld {
-        world: world_arc.clone(),
-        transformers: vec![Box::new(main1_mover)],
+const EXIT_CODES: &str = "\
+Exit codes:
+  0  success
+  1  the scene could not be loaded or exported, or an argument names something that doesn't exist
+  2  invalid command line
+  3  an image could not be written (I/O error)
+  4  the output format is unsupported or encoding failed
+  5  any other image error";
+
+#[derive(Parser)]
+#[command(
+    name = "ray-tracing",
+    about = "Renders scenes following the Ray Tracing in One Weekend series",
+    after_help = EXIT_CODES
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Render a single image
+    Render(SceneArgs),
+    /// Render an animation, writing one image per frame
+    Animate(AnimateArgs),
+    /// Render the same image repeatedly and report the timings
+    Bench(BenchArgs),
+    /// Write the scene, with any overrides applied, to a .toml or .json scene file
+    Export(SceneArgs),
+    /// Print the object, hit point and material under a pixel of the rendered image
+    Pick(PickArgs),
+    /// Trace one sample of a pixel and dump every bounce as JSON, to stdout without --output
+    DebugPath(DebugPathArgs),
+}
+
+#[derive(Args)]
+struct SceneArgs {
+    /// Scene file (.toml, .json, .pbrt, .gltf or .glb), the built-in random scene when omitted
+    scene: Option<PathBuf>,
+    /// Image width in pixels, the height follows the scene's aspect ratio
+    #[arg(long, value_parser = clap::value_parser!(u32).range(2..))]
+    width: Option<u32>,
+    /// Samples per pixel
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    spp: Option<u32>,
+    /// Maximum number of bounces
+    #[arg(long)]
+    depth: Option<u32>,
+    /// Number of worker threads
+    #[arg(long, value_parser = parse_threads)]
+    threads: Option<usize>,
+    /// Seed for scene generation and sampling, makes renders reproducible
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Output image, the format is picked from the extension
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct AnimateArgs {
+    #[command(flatten)]
+    scene: SceneArgs,
+    /// Number of frames to render
+    #[arg(long, default_value_t = 240)]
+    frames: usize,
+    /// Move the camera linearly from the scene's lookfrom to this point, as x,y,z
+    #[arg(long, value_parser = parse_point)]
+    camera_to: Option<Point3>,
+    /// Animate the camera with a .toml or .json file of keyframes
+    #[arg(long, conflicts_with_all = ["camera_to", "orbit", "dolly_zoom"])]
+    keyframes: Option<PathBuf>,
+    /// Circle the camera around lookat by this many degrees
+    #[arg(long, conflicts_with_all = ["camera_to", "dolly_zoom"], allow_hyphen_values = true)]
+    orbit: Option<f64>,
+    /// Move the camera to this point, as x,y,z, while zooming to keep lookat the same size
+    #[arg(long, value_parser = parse_point, conflicts_with = "camera_to")]
+    dolly_zoom: Option<Point3>,
+    /// Move a named object or group linearly to a point, as name=x,y,z. Can be repeated
+    #[arg(long = "move", value_parser = parse_move)]
+    moves: Vec<(String, Point3)>,
+}
+
+#[derive(Args)]
+struct BenchArgs {
+    #[command(flatten)]
+    scene: SceneArgs,
+    /// Number of renders to time
+    #[arg(long, default_value_t = 10)]
+    iterations: u32,
+}
+
+#[derive(Args)]
+struct PickArgs {
+    #[command(flatten)]
+    scene: SceneArgs,
+    /// Pixel as x,y, counted from the top left corner of the image
+    #[arg(long, value_parser = parse_pixel)]
+    pixel: (u32, u32),
+    /// Print the result as JSON, `null` when nothing was hit
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Args)]
+struct DebugPathArgs {
+    #[command(flatten)]
+    scene: SceneArgs,
+    /// Pixel as x,y, counted from the top left corner of the image
+    #[arg(long, value_parser = parse_pixel)]
+    pixel: (u32, u32),
+    /// Which of the pixel's samples to trace, the path matches a render with the same --seed
+    #[arg(long, default_value_t = 0)]
+    sample: u32,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    // their output is meant to be read, possibly by another program
+    let report_done = !matches!(cli.command, Command::Pick(_) | Command::DebugPath(_));
+    let result = match cli.command {
+        Command::Render(args) => render(args),
+        Command::Animate(args) => animate(args),
+        Command::Bench(args) => bench(args),
+        Command::Export(args) => export(args),
+        Command::Pick(args) => pick(args),
+        Command::DebugPath(args) => debug_path(args),
+    };
+    match result {
+        Ok(()) => {
+            if report_done {
+                println!("Done processing!");
+            }
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("error: {}", err);
+            err.exit_code()
+        }
+    }
+}
+
+enum CliError {
+    Scene(String),
+    Image(ImageError),
+}
+
+impl CliError {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            CliError::Scene(_) => ExitCode::from(1),
+            CliError::Image(ImageError::IoError(_)) => ExitCode::from(3),
+            CliError::Image(ImageError::Encoding(_) | ImageError::Unsupported(_)) => {
+                ExitCode::from(4)
+            }
+            CliError::Image(_) => ExitCode::from(5),
+        }
+    }
+}
+
+impl std::fmt::Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CliError::Scene(message) => write!(f, "{}", message),
+            CliError::Image(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<ImageError> for CliError {
+    fn from(err: ImageError) -> Self {
+        CliError::Image(err)
+    }
+}
+
+fn render(args: SceneArgs) -> Result<(), CliError> {
+    let output = output_path(&args, "output.png");
+    let (world, scene_config) = load(&args)?;
+    render_scene(&output, Arc::new(world), Arc::new(scene_config))?;
+    Ok(())
+}
+
+fn animate(args: AnimateArgs) -> Result<(), CliError> {
+    let output = output_path(&args.scene, "frame");
+    let (world, scene_config) = load(&args.scene)?;
+
+    let mut transformers: Vec<Box<dyn WorldTransformer>> = Vec::new();
+    for (name, stopping_point) in args.moves {
+        let starting_point = world.origin(&name).ok_or_else(|| {
+            CliError::Scene(format!("no object or group named `{}` to move", name))
+        })?;
+        transformers.push(Box::new(ObjectMover::new(
+            name,
+            starting_point,
+            stopping_point,
+            args.frames,
+        )));
+    }
+    // the camera transformer also bounds the animation to `frames`
+    let camera_animation = if let Some(path) = &args.keyframes {
+        Some(load_camera_animation(path).map_err(|err| CliError::Scene(err.to_string()))?)
+    } else if let Some(degrees) = args.orbit {
+        Some(CameraAnimation::orbit(&scene_config, degrees, args.frames))
+    } else {
+        args.dolly_zoom
+            .map(|to| CameraAnimation::dolly_zoom(&scene_config, to, args.frames))
     };
-    let lookfrom_b = Point3::new(10.0, 4.0, 5.0);
-    let camera_mover = CameraTransformer::new(lookfrom, lookfrom_b, 240);
-    let scene_transformer = AnimatedConfig {
-        config: scene_config_arc.clone(),
-        transformers: vec![Box::new(camera_mover)],
+    let camera_mover: Box<dyn SceneTransformer> = match camera_animation {
+        Some(animation) => Box::new(KeyframedCamera::new(animation, args.frames)),
+        None => Box::new(CameraTransformer::new(
+            scene_config.lookfrom,
+            args.camera_to.unwrap_or(scene_config.lookfrom),
+            args.frames,
+        )),
     };
+
     let animation = Animation {
-        m_world: world_animation,
-        m_config: scene_transformer,
+        m_world: AnimatedWorld {
+            world: Arc::new(world),
+            transformers,
+        },
+        m_config: AnimatedConfig {
+            config: Arc::new(scene_config),
+            transformers: vec![camera_mover],
+        },
     };
+    animate_scene(&output, animation)?;
+    Ok(())
+}
 
-    // if let Err(e) = animate_scene("test_scene.png", animation) {
-    if let Err(e) = benchmark_scene("output_file.png", world_arc, scene_config_arc, 10) {
-        println!("{}", e);
-    } else {
-        println!("Done processing!");
+fn bench(args: BenchArgs) -> Result<(), CliError> {
+    let output = output_path(&args.scene, "output_file.png");
+    let (world, scene_config) = load(&args.scene)?;
+    benchmark_scene(
+        &output,
+        Arc::new(world),
+        Arc::new(scene_config),
+        args.iterations,
+    )?;
+    Ok(())
+}
+
+fn export(args: SceneArgs) -> Result<(), CliError> {
+    let output = output_path(&args, "scene.toml");
+    let (world, scene_config) = load(&args)?;
+    save_scene(&output, &world, &scene_config).map_err(|err| CliError::Scene(err.to_string()))
+}
+
+fn pick(args: PickArgs) -> Result<(), CliError> {
+    let (world, scene_config) = load(&args.scene)?;
+    let (x, y) = args.pixel;
+    if x >= scene_config.image_width || y >= scene_config.image_height {
+        return Err(CliError::Scene(format!(
+            "pixel {},{} is outside the {}x{} image",
+            x, y, scene_config.image_width, scene_config.image_height
+        )));
+    }
+    let picked = ray_tracing::pick(&world, &scene_config, x, y);
+    if args.json {
+        let json = serde_json::to_string_pretty(&picked).expect("picks serialize");
+        println!("{}", json);
+        return Ok(());
+    }
+    match picked {
+        Some(picked) => {
+            let material = match &picked.material {
+                Some(material) => serde_json::to_string(material).expect("materials serialize"),
+                None => "unknown".to_string(),
+            };
+            println!("object:   {}", picked.name);
+            println!("point:    {}", format_vec(picked.point));
+            println!("normal:   {}", format_vec(picked.normal));
+            println!("distance: {}", picked.distance);
+            println!("material: {}", material);
+        }
+        None => println!("nothing under pixel {},{}", x, y),
+    }
+    Ok(())
+}
+
+fn debug_path(args: DebugPathArgs) -> Result<(), CliError> {
+    let (world, scene_config) = load(&args.scene)?;
+    let (x, y) = args.pixel;
+    let trace =
+        trace_path(&Arc::new(world), &scene_config, x, y, args.sample).ok_or_else(|| {
+            CliError::Scene(format!(
+                "pixel {},{} sample {} is outside the {}x{} image with {} samples per pixel",
+                x,
+                y,
+                args.sample,
+                scene_config.image_width,
+                scene_config.image_height,
+                scene_config.samples_per_pixel
+            ))
+        })?;
+    let json = serde_json::to_string_pretty(&trace).expect("paths serialize");
+    match &args.scene.output {
+        Some(path) => fs::write(path, json + "\n")
+            .map_err(|err| CliError::Scene(format!("unable to write {}: {}", path.display(), err))),
+        None => {
+            println!("{}", json);
+            Ok(())
+        }
+    }
+}
+
+fn format_vec(v: Point3) -> String {
+    format!("{}, {}, {}", v.x(), v.y(), v.z())
+}
+
+fn load(args: &SceneArgs) -> Result<(HittableMap, SceneConfig), CliError> {
+    if let Some(seed) = args.seed {
+        seed_rng(seed);
+    }
+    let (world, mut scene_config) = match &args.scene {
+        Some(path) if path.extension().is_some_and(|ext| ext == "pbrt") => {
+            let import = load_pbrt(path).map_err(|err| CliError::Scene(err.to_string()))?;
+            for warning in &import.warnings {
+                eprintln!("warning: {}", warning);
+            }
+            (import.scene.world, import.scene.config)
+        }
+        Some(path)
+            if path
+                .extension()
+                .is_some_and(|ext| ext == "gltf" || ext == "glb") =>
+        {
+            let import = load_gltf(path).map_err(|err| CliError::Scene(err.to_string()))?;
+            for warning in &import.warnings {
+                eprintln!("warning: {}: {}", path.display(), warning);
+            }
+            (import.scene.world, import.scene.config)
+        }
+        Some(path) => {
+            let scene = load_scene(path).map_err(|err| CliError::Scene(err.to_string()))?;
+            (scene.world, scene.config)
+        }
+        None => (random_scene(), SceneConfig::default()),
+    };
+
+    if let Some(width) = args.width {
+        scene_config.image_width = width;
+        scene_config.image_height = (width as f64 / scene_config.aspect_ratio) as u32;
+        if scene_config.image_height < 2 {
+            return Err(CliError::Scene(format!(
+                "image must be at least 2x2 pixels, got {}x{} from --width and the aspect ratio {}",
+                width, scene_config.image_height, scene_config.aspect_ratio
+            )));
+        }
+    }
+    if let Some(spp) = args.spp {
+        scene_config.samples_per_pixel = spp;
+    }
+    if let Some(depth) = args.depth {
+        scene_config.max_depth = depth;
+    }
+    if let Some(threads) = args.threads {
+        scene_config.num_threads = threads;
+    }
+    if args.seed.is_some() {
+        scene_config.seed = args.seed;
+    }
+    if let Some(region) = &scene_config.region {
+        if !region.fits(scene_config.image_width, scene_config.image_height) {
+            return Err(CliError::Scene(format!(
+                "region {}x{} at {},{} isn't inside the {}x{} image",
+                region.width,
+                region.height,
+                region.x,
+                region.y,
+                scene_config.image_width,
+                scene_config.image_height
+            )));
+        }
+    }
+    // after the overrides, a focus pixel depends on the image size
+    let scene_config = scene_config.focused(&world);
+    Ok((world, scene_config))
+}
+
+fn output_path(args: &SceneArgs, default: &str) -> String {
+    args.output
+        .as_ref()
+        .map_or_else(|| default.to_string(), |path| path.display().to_string())
+}
+
+fn parse_point(value: &str) -> Result<Point3, String> {
+    let coords = value
+        .split(',')
+        .map(|coord| coord.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| err.to_string())?;
+    match coords[..] {
+        [x, y, z] => Ok(Point3::new(x, y, z)),
+        _ => Err(format!("expected x,y,z but got `{}`", value)),
+    }
+}
+
+fn parse_threads(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>().map_err(|err| err.to_string())? {
+        0 => Err("at least 1 thread is needed".to_string()),
+        threads => Ok(threads),
     }
 }
+
+fn parse_pixel(value: &str) -> Result<(u32, u32), String> {
+    let (x, y) = value
+        .split_once(',')
+        .ok_or_else(|| format!("expected x,y but got `{}`", value))?;
+    let coord = |coord: &str| coord.trim().parse::<u32>().map_err(|err| err.to_string());
+    Ok((coord(x)?, coord(y)?))
+}
+
+fn parse_move(value: &str) -> Result<(String, Point3), String> {
+    let (name, point) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected name=x,y,z but got `{}`", value))?;
+    Ok((name.to_string(), parse_point(point)?))
+}
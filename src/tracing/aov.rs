@@ -0,0 +1,407 @@
+use std::path::{Path, PathBuf};
+
+use exr::prelude::{
+    AnyChannel, AnyChannels, Encoding, FlatSamples, Image, Layer, LayerAttributes, WritableImage,
+};
+use image::{
+    error::{EncodingError, ImageFormatHint},
+    ImageError, ImageFormat, ImageResult,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{luminance, Color, FrameBuffer, Point3, RenderRegion, Vec3};
+
+/// What the primary ray of a single sample saw.
+#[derive(Debug, Copy, Clone)]
+pub struct AovSample {
+    pub albedo: Color,
+    pub normal: Vec3,
+    /// Distance from the camera to the hit point.
+    pub depth: f64,
+    pub position: Point3,
+    pub object_id: Option<u32>,
+    pub hit: bool,
+    /// Variance of the pixel's mean luminance, only known once a pixel is
+    /// resolved, and infinite when a single sample leaves it unknown.
+    pub variance: f64,
+}
+
+impl AovSample {
+    pub fn miss(background: Color) -> AovSample {
+        AovSample {
+            albedo: background,
+            normal: Vec3::empty(),
+            depth: f64::INFINITY,
+            position: Point3::empty(),
+            object_id: None,
+            hit: false,
+            variance: 0.0,
+        }
+    }
+}
+
+/// Which auxiliary buffers get written next to the beauty image.
+#[derive(Debug, Copy, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AovOutput {
+    #[default]
+    None,
+    /// One `<stem>_<aov>.exr` file per buffer.
+    SeparateFiles,
+    /// A single EXR holding the beauty and all buffers as `<aov>.<channel>`
+    /// layers. Written over the beauty output if that is an `.exr`, otherwise
+    /// to `<stem>_layers.exr`.
+    MultiLayerExr,
+}
+
+/// Averages the AOV samples of one pixel.
+///
+/// Albedo is averaged over every sample, geometric values only over the
+/// samples that hit something. The object id is the one of the first sample
+/// that hit, ids can't be blended. The variance is estimated from the
+/// luminance of the pixel's own samples, it takes at least two.
+#[derive(Debug, Copy, Clone)]
+pub struct AovPixel {
+    albedo: Color,
+    luminance: f64,
+    luminance_squared: f64,
+    normal: Vec3,
+    depth: f64,
+    position: Point3,
+    object_id: Option<u32>,
+    samples: u32,
+    hits: u32,
+}
+
+impl AovPixel {
+    pub fn new() -> AovPixel {
+        AovPixel {
+            albedo: Color::empty(),
+            luminance: 0.0,
+            luminance_squared: 0.0,
+            normal: Vec3::empty(),
+            depth: 0.0,
+            position: Point3::empty(),
+            object_id: None,
+            samples: 0,
+            hits: 0,
+        }
+    }
+    pub fn add(&mut self, sample: &AovSample, color: Color) {
+        let lum = luminance(&color);
+        self.samples += 1;
+        self.albedo += sample.albedo;
+        self.luminance += lum;
+        self.luminance_squared += lum * lum;
+        if sample.hit {
+            self.hits += 1;
+            self.normal += sample.normal;
+            self.depth += sample.depth;
+            self.position += sample.position;
+            self.object_id = self.object_id.or(sample.object_id);
+        }
+    }
+    pub fn resolve(&self) -> AovSample {
+        let mut sample = AovSample::miss(self.albedo / self.samples.max(1) as f64);
+        if self.samples > 1 {
+            let n = self.samples as f64;
+            let mean = self.luminance / n;
+            let sample_variance = (self.luminance_squared / n - mean * mean) * n / (n - 1.0);
+            sample.variance = sample_variance.max(0.0) / n;
+        } else {
+            sample.variance = f64::INFINITY;
+        }
+        if self.hits > 0 {
+            let hits = self.hits as f64;
+            sample.normal = if self.normal.near_zero() {
+                self.normal
+            } else {
+                self.normal.unit_vector()
+            };
+            sample.depth = self.depth / hits;
+            sample.position = self.position / hits;
+            sample.object_id = self.object_id;
+            sample.hit = true;
+        }
+        sample
+    }
+}
+
+impl Default for AovPixel {
+    fn default() -> Self {
+        AovPixel::new()
+    }
+}
+
+/// First hit albedo, shading normal, depth, world position and object id per pixel.
+#[derive(Debug, Clone)]
+pub struct AovBuffers {
+    pub albedo: FrameBuffer,
+    pub normal: FrameBuffer,
+    pub position: FrameBuffer,
+    pub depth: Vec<f32>,
+    pub variance: Vec<f32>,
+    /// `HittableMap::object_id` of the object under the pixel, 0 for the background.
+    pub object_id: Vec<u32>,
+}
+
+impl AovBuffers {
+    pub fn new(width: u32, height: u32) -> AovBuffers {
+        let size = (width * height) as usize;
+        AovBuffers {
+            albedo: FrameBuffer::new(width, height),
+            normal: FrameBuffer::new(width, height),
+            position: FrameBuffer::new(width, height),
+            depth: vec![f32::INFINITY; size],
+            variance: vec![0.0; size],
+            object_id: vec![0; size],
+        }
+    }
+    pub fn width(&self) -> u32 {
+        self.albedo.width()
+    }
+    pub fn height(&self) -> u32 {
+        self.albedo.height()
+    }
+    pub fn put_pixel(&mut self, x: u32, y: u32, sample: &AovSample) {
+        let ix = (y * self.width() + x) as usize;
+        self.albedo.put_pixel(x, y, sample.albedo);
+        self.normal.put_pixel(x, y, sample.normal);
+        self.position.put_pixel(x, y, sample.position);
+        self.depth[ix] = sample.depth as f32;
+        self.variance[ix] = sample.variance as f32;
+        self.object_id[ix] = sample.object_id.unwrap_or(0);
+    }
+    pub fn get_pixel(&self, x: u32, y: u32) -> AovSample {
+        let ix = (y * self.width() + x) as usize;
+        AovSample {
+            albedo: self.albedo.get_pixel(x, y),
+            normal: self.normal.get_pixel(x, y),
+            depth: self.depth[ix] as f64,
+            position: self.position.get_pixel(x, y),
+            object_id: Some(self.object_id[ix]).filter(|id| *id != 0),
+            hit: self.depth[ix].is_finite(),
+            variance: self.variance[ix] as f64,
+        }
+    }
+
+    /// Just the pixels in `region`.
+    pub fn crop(&self, region: &RenderRegion) -> AovBuffers {
+        let mut cropped = AovBuffers::new(region.width, region.height);
+        for y in region.rows() {
+            for x in region.columns() {
+                cropped.put_pixel(x - region.x, y - region.y, &self.get_pixel(x, y));
+            }
+        }
+        cropped
+    }
+
+    /// Writes the buffers requested by `output` next to `output_file`.
+    pub fn save(
+        &self,
+        output: AovOutput,
+        output_file: &Path,
+        beauty: &FrameBuffer,
+    ) -> ImageResult<()> {
+        match output {
+            AovOutput::None => Ok(()),
+            AovOutput::SeparateFiles => {
+                for (aov, channels) in self.channels() {
+                    let path = sibling_path(output_file, &format!("_{}.exr", aov));
+                    write_exr(&path, self.width(), self.height(), channels)?;
+                }
+                Ok(())
+            }
+            AovOutput::MultiLayerExr => {
+                let path = if output_file
+                    .extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("exr"))
+                {
+                    output_file.to_path_buf()
+                } else {
+                    sibling_path(output_file, "_layers.exr")
+                };
+                let mut channels = rgb_channels(beauty);
+                for (aov, aov_channels) in self.channels() {
+                    channels.extend(aov_channels.into_iter().map(|mut channel| {
+                        channel.name = format!("{}.{}", aov, channel.name).as_str().into();
+                        channel
+                    }));
+                }
+                write_exr(&path, self.width(), self.height(), channels)
+            }
+        }
+    }
+
+    fn channels(&self) -> Vec<(&'static str, Vec<AnyChannel<FlatSamples>>)> {
+        vec![
+            ("albedo", rgb_channels(&self.albedo)),
+            ("normal", rgb_channels(&self.normal)),
+            ("position", rgb_channels(&self.position)),
+            (
+                "depth",
+                vec![AnyChannel::new("Z", FlatSamples::F32(self.depth.clone()))],
+            ),
+            (
+                "variance",
+                vec![AnyChannel::new(
+                    "V",
+                    FlatSamples::F32(self.variance.clone()),
+                )],
+            ),
+            (
+                "object",
+                vec![AnyChannel::new(
+                    "id",
+                    FlatSamples::U32(self.object_id.clone()),
+                )],
+            ),
+        ]
+    }
+}
+
+fn rgb_channels(framebuffer: &FrameBuffer) -> Vec<AnyChannel<FlatSamples>> {
+    let pixels = framebuffer.as_rgb32f();
+    (0..3)
+        .map(|channel| {
+            let samples = pixels.pixels().map(|pixel| pixel.0[channel]).collect();
+            AnyChannel::new(["R", "G", "B"][channel], FlatSamples::F32(samples))
+        })
+        .collect()
+}
+
+fn write_exr(
+    path: &Path,
+    width: u32,
+    height: u32,
+    channels: Vec<AnyChannel<FlatSamples>>,
+) -> ImageResult<()> {
+    let layer = Layer::new(
+        (width as usize, height as usize),
+        LayerAttributes::default(),
+        Encoding::FAST_LOSSLESS,
+        AnyChannels::sort(channels.into()),
+    );
+    Image::from_layer(layer)
+        .write()
+        .to_file(path)
+        .map_err(|err| {
+            ImageError::Encoding(EncodingError::new(
+                ImageFormatHint::Exact(ImageFormat::OpenExr),
+                err,
+            ))
+        })
+}
+
+/// `dir/stem.ext` -> `dir/stem<suffix>`
+pub(crate) fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+    path.with_file_name(format!("{}{}", stem, suffix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(albedo: Color, normal: Vec3, depth: f64, object_id: u32) -> AovSample {
+        AovSample {
+            albedo,
+            normal,
+            depth,
+            position: Point3::new(0.0, 0.0, -depth),
+            object_id: Some(object_id),
+            hit: true,
+            variance: 0.0,
+        }
+    }
+
+    fn grey(value: f64) -> Color {
+        Color::new(value, value, value)
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn averages_geometry_over_hits_only() {
+        let mut pixel = AovPixel::new();
+        pixel.add(&hit(grey(1.0), Vec3::new(0.0, 1.0, 0.0), 2.0, 7), grey(1.0));
+        pixel.add(&AovSample::miss(grey(0.0)), grey(1.0));
+        pixel.add(&hit(grey(0.5), Vec3::new(1.0, 0.0, 0.0), 4.0, 9), grey(1.0));
+        let resolved = pixel.resolve();
+
+        assert!(resolved.hit);
+        // albedo over all three samples, the rest over the two hits
+        assert_close(resolved.albedo.x(), 0.5);
+        assert_close(resolved.depth, 3.0);
+        assert_close(resolved.position.z(), -3.0);
+        assert_close(resolved.normal.length(), 1.0);
+        assert_close(resolved.normal.x(), resolved.normal.y());
+        assert_eq!(resolved.object_id, Some(7));
+    }
+
+    #[test]
+    fn misses_resolve_to_the_background() {
+        let mut pixel = AovPixel::new();
+        pixel.add(&AovSample::miss(grey(0.25)), grey(0.25));
+        pixel.add(&AovSample::miss(grey(0.75)), grey(0.75));
+        let resolved = pixel.resolve();
+
+        assert!(!resolved.hit);
+        assert_eq!(resolved.depth, f64::INFINITY);
+        assert_eq!(resolved.object_id, None);
+        assert_close(resolved.albedo.y(), 0.5);
+    }
+
+    #[test]
+    fn variance_is_the_variance_of_the_mean() {
+        let mut pixel = AovPixel::new();
+        pixel.add(&AovSample::miss(grey(0.0)), grey(1.0));
+        assert_eq!(pixel.resolve().variance, f64::INFINITY);
+
+        // luminances 1 and 3, a sample variance of 2 over 2 samples
+        pixel.add(&AovSample::miss(grey(0.0)), grey(3.0));
+        assert!((pixel.resolve().variance - 1.0).abs() < 1e-6);
+
+        let mut constant = AovPixel::new();
+        for _ in 0..4 {
+            constant.add(&AovSample::miss(grey(0.0)), grey(0.5));
+        }
+        assert!(constant.resolve().variance.abs() < 1e-12);
+    }
+
+    #[test]
+    fn buffers_round_trip_samples() {
+        let mut buffers = AovBuffers::new(3, 2);
+        let sample = AovSample {
+            variance: 0.125,
+            ..hit(grey(0.5), Vec3::new(0.0, 0.0, 1.0), 2.5, 4)
+        };
+        buffers.put_pixel(2, 1, &sample);
+
+        let read = buffers.get_pixel(2, 1);
+        assert!(read.hit);
+        assert_eq!(read.albedo, sample.albedo);
+        assert_eq!(read.normal, sample.normal);
+        assert_eq!(read.position, sample.position);
+        assert_eq!(read.depth, 2.5);
+        assert_eq!(read.variance, 0.125);
+        assert_eq!(read.object_id, Some(4));
+
+        // untouched pixels read back as background
+        let background = buffers.get_pixel(0, 0);
+        assert!(!background.hit);
+        assert_eq!(background.object_id, None);
+    }
+
+    #[test]
+    fn sibling_paths_replace_the_extension() {
+        assert_eq!(
+            sibling_path(Path::new("out/render.png"), "_albedo.exr"),
+            PathBuf::from("out/render_albedo.exr")
+        );
+    }
+}
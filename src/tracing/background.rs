@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Color, Ray};
+
+/// What rays that escape the scene see.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Background {
+    Solid {
+        color: Color,
+    },
+    /// Blend from `bottom` (straight down) to `top` (straight up) along the ray's y.
+    Gradient {
+        bottom: Color,
+        top: Color,
+    },
+}
+
+impl Background {
+    pub fn color(&self, ray: &Ray) -> Color {
+        match *self {
+            Background::Solid { color } => color,
+            Background::Gradient { bottom, top } => {
+                let unit_direction = ray.direction().unit_vector();
+                let t = 0.5 * (unit_direction.y() + 1.0);
+                (1.0 - t) * bottom + t * top
+            }
+        }
+    }
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Gradient {
+            bottom: Color::new(1.0, 1.0, 1.0),
+            top: Color::new(0.5, 0.7, 1.0),
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use image::ImageResult;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{rng, Vec3};
+
+/// Shape of out of focus highlights, set by the lens opening.
+///
+/// ```toml
+/// [config.bokeh]
+/// shape = { type = "polygon", blades = 6, rotation = 15.0 }
+/// cats_eye = 0.5
+/// anamorphic_squeeze = 2.0
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Bokeh {
+    #[serde(default)]
+    pub shape: ApertureShape,
+    /// Cat's eye vignetting, 0 keeps the shape everywhere, 1 cuts highlights
+    /// in the image corners down to a narrow lens shape.
+    #[serde(default)]
+    pub cats_eye: f64,
+    /// Anamorphic squeeze factor, the opening is narrowed horizontally by it
+    /// so highlights come out tall and oval.
+    #[serde(default = "unit_squeeze")]
+    pub anamorphic_squeeze: f64,
+}
+
+fn unit_squeeze() -> f64 {
+    1.0
+}
+
+impl Default for Bokeh {
+    fn default() -> Self {
+        Bokeh {
+            shape: ApertureShape::default(),
+            cats_eye: 0.0,
+            anamorphic_squeeze: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ApertureShape {
+    #[default]
+    Circle,
+    /// Regular polygon formed by `blades` straight blades, `rotation` in
+    /// degrees turns it counterclockwise.
+    Polygon {
+        blades: u32,
+        #[serde(default)]
+        rotation: f64,
+    },
+    /// Grayscale image of the opening, brighter pixels let more light
+    /// through. Scene files resolve `file` relative to themselves, in code
+    /// use `ApertureShape::mask` so the image is loaded.
+    Mask {
+        file: PathBuf,
+        #[serde(skip)]
+        mask: Option<Arc<ApertureMask>>,
+    },
+}
+
+impl ApertureShape {
+    pub fn mask<P: AsRef<Path>>(file: P) -> ImageResult<ApertureShape> {
+        let file = file.as_ref();
+        let mask = ApertureMask::load(file)?;
+        Ok(ApertureShape::Mask {
+            // absolute, so exported scenes find the file wherever they're written
+            file: std::fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf()),
+            mask: Some(Arc::new(mask)),
+        })
+    }
+}
+
+/// Aperture image turned into a distribution to sample lens positions from.
+#[derive(Debug)]
+pub struct ApertureMask {
+    width: usize,
+    height: usize,
+    // running sum of the pixel values, row by row from the top
+    cdf: Vec<f64>,
+}
+
+impl ApertureMask {
+    pub fn load<P: AsRef<Path>>(file: P) -> ImageResult<ApertureMask> {
+        let image = image::open(file)?.to_luma32f();
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let mut total = 0.0;
+        let cdf = image
+            .pixels()
+            .map(|pixel| {
+                total += pixel.0[0].max(0.0) as f64;
+                total
+            })
+            .collect();
+        Ok(ApertureMask { width, height, cdf })
+    }
+
+    /// `false` for a black image, which lets no light through.
+    pub fn is_open(&self) -> bool {
+        self.cdf.last().is_some_and(|total| *total > 0.0)
+    }
+
+    // Point in [-1, 1]², the longer image side spans the full range.
+    fn sample(&self) -> (f64, f64) {
+        let mut rng = rng();
+        let total = self.cdf.last().copied().unwrap_or(0.0);
+        let target = rng.gen::<f64>() * total;
+        let ix = self
+            .cdf
+            .partition_point(|sum| *sum <= target)
+            .min(self.cdf.len() - 1);
+        let size = self.width.max(self.height) as f64;
+        let x = (ix % self.width) as f64 + rng.gen::<f64>();
+        let y = (ix / self.width) as f64 + rng.gen::<f64>();
+        (
+            (2.0 * x - self.width as f64) / size,
+            (self.height as f64 - 2.0 * y) / size,
+        )
+    }
+}
+
+impl Bokeh {
+    /// Point on the unit aperture for a ray through image position `u`, `v`.
+    pub fn sample(&self, u: f64, v: f64, aspect_ratio: f64) -> (f64, f64) {
+        // the image center is never clipped
+        let (cx, cy) = match self.cats_eye > 0.0 {
+            true => {
+                let norm = (aspect_ratio * aspect_ratio + 1.0).sqrt();
+                let scale = self.cats_eye / norm;
+                (
+                    (2.0 * u - 1.0) * aspect_ratio * scale,
+                    (2.0 * v - 1.0) * scale,
+                )
+            }
+            false => (0.0, 0.0),
+        };
+        let (x, y) = match cx == 0.0 && cy == 0.0 {
+            true => self.sample_shape(),
+            false => self.sample_clipped(cx, cy),
+        };
+        (x / self.anamorphic_squeeze, y)
+    }
+
+    // The opening as seen through a second, offset pupil, rejection sampled.
+    fn sample_clipped(&self, cx: f64, cy: f64) -> (f64, f64) {
+        for _ in 0..64 {
+            let (x, y) = self.sample_shape();
+            if (x - cx).powi(2) + (y - cy).powi(2) <= 1.0 {
+                return (x, y);
+            }
+        }
+        (cx / 2.0, cy / 2.0)
+    }
+
+    fn sample_shape(&self) -> (f64, f64) {
+        match &self.shape {
+            ApertureShape::Polygon { blades, rotation } if *blades >= 3 => {
+                sample_polygon(*blades, *rotation)
+            }
+            ApertureShape::Mask {
+                mask: Some(mask), ..
+            } => mask.sample(),
+            // also too few blades to form a polygon, or a mask that wasn't loaded
+            _ => {
+                let p = Vec3::random_in_unit_disk();
+                (p.x(), p.y())
+            }
+        }
+    }
+}
+
+// Uniform point in the regular polygon inscribed in the unit circle, made of
+// equally sized triangles around the center.
+fn sample_polygon(blades: u32, rotation: f64) -> (f64, f64) {
+    let mut rng = rng();
+    let step = 2.0 * std::f64::consts::PI / blades as f64;
+    let triangle = rng.gen_range(0..blades) as f64;
+    let a = rotation.to_radians() + triangle * step;
+    let b = a + step;
+    let (mut s, mut t) = (rng.gen::<f64>(), rng.gen::<f64>());
+    if s + t > 1.0 {
+        (s, t) = (1.0 - s, 1.0 - t);
+    }
+    (s * a.cos() + t * b.cos(), s * a.sin() + t * b.sin())
+}
@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{pick, HittableMap, Point3, SceneConfig};
+
+use super::basis;
+
+/// Where the lens focuses, resolved again for every frame so the focus
+/// follows the camera and the scene as they move.
+///
+/// ```toml
+/// [config]
+/// focus = { type = "object", name = "car/body" }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Focus {
+    /// `dist_to_focus` as it is.
+    #[default]
+    Fixed,
+    /// The plane through `lookat`.
+    Lookat,
+    /// The plane through the origin of a group or object in the `HittableMap`.
+    Object { name: String },
+    /// Whatever the camera sees first through pixel `x`, `y`, counted from
+    /// the top left corner of the image.
+    Pixel { x: u32, y: u32 },
+}
+
+impl Focus {
+    /// Distance from `lookfrom` to the focus plane along the view direction,
+    /// `None` for `Fixed` or when there's nothing in front of the camera
+    /// to focus on.
+    pub fn distance(&self, world: &HittableMap, config: &SceneConfig) -> Option<f64> {
+        let target = match self {
+            Focus::Fixed => return None,
+            Focus::Lookat => config.lookat,
+            Focus::Object { name } => world.world_origin(name)?,
+            Focus::Pixel { x, y } => pick(world, config, *x, *y)?.point,
+        };
+        Some(depth(config, target)).filter(|depth| *depth > 0.0)
+    }
+}
+
+// depth along the view direction, the focus plane faces the camera
+fn depth(config: &SceneConfig, point: Point3) -> f64 {
+    let (_, _, cw) = basis(config.lookfrom, config.lookat, config.vup);
+    (config.lookfrom - point).dot(&cw)
+}
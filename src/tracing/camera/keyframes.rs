@@ -0,0 +1,401 @@
+use std::ops::{Add, Mul, Sub};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Point3, SceneConfig, SceneTransformer, Vec3};
+
+/// How a channel moves between two of its keyframes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Interpolation {
+    /// Holds each value until the next keyframe.
+    Step,
+    #[default]
+    Linear,
+    /// Eases in and out of every keyframe.
+    Smooth,
+    /// Smooth curve through all keyframes, without stopping at them.
+    CatmullRom,
+}
+
+/// Camera values at one frame, channels that aren't set are interpolated
+/// from the keyframes around it that set them.
+#[derive(Debug, Copy, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraKeyframe {
+    /// Counted from 0, the first rendered frame.
+    pub frame: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lookfrom: Option<Point3>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lookat: Option<Point3>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vup: Option<Vec3>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vfov: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aperature: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dist_to_focus: Option<f64>,
+}
+
+/// Interpolation per animated channel.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChannelInterpolation {
+    pub lookfrom: Interpolation,
+    pub lookat: Interpolation,
+    pub vup: Interpolation,
+    pub vfov: Interpolation,
+    pub aperature: Interpolation,
+    pub dist_to_focus: Interpolation,
+}
+
+/// Keyframed camera moves, as read by `load_camera_animation`.
+///
+/// ```toml
+/// [interpolation]
+/// lookfrom = "catmull_rom"
+///
+/// [[keyframes]]
+/// frame = 0
+/// lookfrom = [13.0, 2.0, 3.0]
+/// vfov = 20.0
+///
+/// [[keyframes]]
+/// frame = 120
+/// lookfrom = [3.0, 2.0, 13.0]
+/// vfov = 35.0
+/// ```
+///
+/// Before its first and after its last keyframe a channel holds still,
+/// channels without keyframes keep the scene's value.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraAnimation {
+    #[serde(default)]
+    pub interpolation: ChannelInterpolation,
+    pub keyframes: Vec<CameraKeyframe>,
+}
+
+impl CameraAnimation {
+    /// Circles `lookfrom` around `lookat` by `degrees` over `frames`, turning
+    /// around `vup`. The last frame ends the turn, except for whole turns,
+    /// which loop by stopping a step short of the first frame.
+    pub fn orbit(config: &SceneConfig, degrees: f64, frames: usize) -> CameraAnimation {
+        let axis = config.vup.unit_vector();
+        let arm = config.lookfrom - config.lookat;
+        let steps = if degrees % 360.0 == 0.0 {
+            frames.max(1)
+        } else {
+            frames.saturating_sub(1).max(1)
+        } as f64;
+        let keyframes = (0..frames)
+            .map(|frame| {
+                let angle = (degrees * frame as f64 / steps).to_radians();
+                // Rodrigues' rotation of the arm around the axis
+                let rotated = arm * angle.cos()
+                    + axis.cross(&arm) * angle.sin()
+                    + axis * (axis.dot(&arm) * (1.0 - angle.cos()));
+                CameraKeyframe {
+                    frame,
+                    lookfrom: Some(config.lookat + rotated),
+                    ..Default::default()
+                }
+            })
+            .collect();
+        CameraAnimation {
+            interpolation: ChannelInterpolation::default(),
+            keyframes,
+        }
+    }
+
+    /// Moves `lookfrom` in a straight line to `lookfrom_to` over `frames`
+    /// while `vfov` changes to keep the view at `lookat` the same size, and
+    /// focus stays on `lookat`.
+    pub fn dolly_zoom(config: &SceneConfig, lookfrom_to: Point3, frames: usize) -> CameraAnimation {
+        let distance = (config.lookat - config.lookfrom).length();
+        let half_height = distance * (config.vfov.to_radians() / 2.0).tan();
+        let last = frames.saturating_sub(1).max(1) as f64;
+        let keyframes = (0..frames)
+            .map(|frame| {
+                let lookfrom =
+                    config.lookfrom + (lookfrom_to - config.lookfrom) * (frame as f64 / last);
+                let distance = (config.lookat - lookfrom).length();
+                CameraKeyframe {
+                    frame,
+                    lookfrom: Some(lookfrom),
+                    vfov: Some(2.0 * (half_height / distance).atan().to_degrees()),
+                    dist_to_focus: Some(distance),
+                    ..Default::default()
+                }
+            })
+            .collect();
+        CameraAnimation {
+            interpolation: ChannelInterpolation::default(),
+            keyframes,
+        }
+    }
+
+    /// `config` with every keyframed channel set to its value at `frame`.
+    pub fn camera_at(&self, config: &SceneConfig, frame: usize) -> SceneConfig {
+        let mut keyframes = self.keyframes.clone();
+        keyframes.sort_by_key(|keyframe| keyframe.frame);
+        let frame = frame as f64;
+        let mut config = config.clone();
+        let (keys, ease) = (&keyframes, self.interpolation);
+        animate(
+            &mut config.lookfrom,
+            keys,
+            |key| key.lookfrom,
+            ease.lookfrom,
+            frame,
+        );
+        animate(
+            &mut config.lookat,
+            keys,
+            |key| key.lookat,
+            ease.lookat,
+            frame,
+        );
+        animate(&mut config.vup, keys, |key| key.vup, ease.vup, frame);
+        animate(&mut config.vfov, keys, |key| key.vfov, ease.vfov, frame);
+        animate(
+            &mut config.aperature,
+            keys,
+            |key| key.aperature,
+            ease.aperature,
+            frame,
+        );
+        let dist_to_focus = |key: &CameraKeyframe| key.dist_to_focus;
+        animate(
+            &mut config.dist_to_focus,
+            keys,
+            dist_to_focus,
+            ease.dist_to_focus,
+            frame,
+        );
+        config
+    }
+}
+
+/// Scene transformer playing a `CameraAnimation` for `frames` frames.
+pub struct KeyframedCamera {
+    t: usize,
+    frames: usize,
+    animation: CameraAnimation,
+}
+
+impl KeyframedCamera {
+    pub fn new(animation: CameraAnimation, frames: usize) -> KeyframedCamera {
+        KeyframedCamera {
+            t: 0,
+            frames,
+            animation,
+        }
+    }
+}
+
+impl SceneTransformer for KeyframedCamera {
+    fn transform(&mut self, scene_config: &SceneConfig) -> Option<SceneConfig> {
+        if self.t >= self.frames {
+            None
+        } else {
+            let new_scene = self.animation.camera_at(scene_config, self.t);
+            self.t += 1;
+            Some(new_scene)
+        }
+    }
+}
+
+// Values that can be interpolated.
+trait Channel: Copy + Add<Output = Self> + Sub<Output = Self> + Mul<f64, Output = Self> {}
+
+impl<T> Channel for T where T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f64, Output = T> {}
+
+// Sets `target` to the channel picked by `value` at `frame`, if any keyframe
+// sets it.
+fn animate<T: Channel>(
+    target: &mut T,
+    keyframes: &[CameraKeyframe],
+    value: impl Fn(&CameraKeyframe) -> Option<T>,
+    interpolation: Interpolation,
+    frame: f64,
+) {
+    if let Some(animated) = evaluate(&channel_keys(keyframes, value), frame, interpolation) {
+        *target = animated;
+    }
+}
+
+// Keys of one channel by frame, a later keyframe on the same frame wins.
+fn channel_keys<T>(
+    keyframes: &[CameraKeyframe],
+    value: impl Fn(&CameraKeyframe) -> Option<T>,
+) -> Vec<(f64, T)> {
+    let mut keys: Vec<(f64, T)> = Vec::new();
+    for keyframe in keyframes {
+        if let Some(value) = value(keyframe) {
+            let frame = keyframe.frame as f64;
+            match keys.last_mut() {
+                Some(last) if last.0 == frame => last.1 = value,
+                _ => keys.push((frame, value)),
+            }
+        }
+    }
+    keys
+}
+
+fn evaluate<T: Channel>(keys: &[(f64, T)], frame: f64, interpolation: Interpolation) -> Option<T> {
+    let (first, last) = (keys.first()?, keys.last()?);
+    if frame <= first.0 {
+        return Some(first.1);
+    }
+    if frame >= last.0 {
+        return Some(last.1);
+    }
+    // keys[k] <= frame < keys[k + 1]
+    let k = keys.partition_point(|(key_frame, _)| *key_frame <= frame) - 1;
+    let ((t1, p1), (t2, p2)) = (keys[k], keys[k + 1]);
+    let h = t2 - t1;
+    let s = (frame - t1) / h;
+    Some(match interpolation {
+        Interpolation::Step => p1,
+        Interpolation::Linear => p1 + (p2 - p1) * s,
+        Interpolation::Smooth => p1 + (p2 - p1) * (s * s * (3.0 - 2.0 * s)),
+        Interpolation::CatmullRom => {
+            // tangents from the neighbouring keys, one sided at the ends
+            let tangent = |ix: usize| {
+                let (prev, next) = (
+                    keys[ix.saturating_sub(1)],
+                    keys[(ix + 1).min(keys.len() - 1)],
+                );
+                (next.1 - prev.1) * (1.0 / (next.0 - prev.0))
+            };
+            let (m1, m2) = (tangent(k), tangent(k + 1));
+            let (s2, s3) = (s * s, s * s * s);
+            p1 * (2.0 * s3 - 3.0 * s2 + 1.0)
+                + m1 * (h * (s3 - 2.0 * s2 + s))
+                + p2 * (3.0 * s2 - 2.0 * s3)
+                + m2 * (h * (s3 - s2))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEYS: [(f64, f64); 3] = [(10.0, 1.0), (20.0, 3.0), (40.0, -1.0)];
+
+    const ALL: [Interpolation; 4] = [
+        Interpolation::Step,
+        Interpolation::Linear,
+        Interpolation::Smooth,
+        Interpolation::CatmullRom,
+    ];
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn holds_the_end_keys_outside_the_keyframes() {
+        for interpolation in ALL {
+            assert_eq!(evaluate(&KEYS, 0.0, interpolation), Some(1.0));
+            assert_eq!(evaluate(&KEYS, 10.0, interpolation), Some(1.0));
+            assert_eq!(evaluate(&KEYS, 40.0, interpolation), Some(-1.0));
+            assert_eq!(evaluate(&KEYS, 100.0, interpolation), Some(-1.0));
+        }
+    }
+
+    #[test]
+    fn passes_through_every_key() {
+        for interpolation in ALL {
+            assert_close(evaluate(&KEYS, 20.0, interpolation).unwrap(), 3.0);
+        }
+    }
+
+    #[test]
+    fn interpolates_between_keys() {
+        assert_eq!(evaluate(&KEYS, 19.0, Interpolation::Step), Some(1.0));
+        assert_close(evaluate(&KEYS, 15.0, Interpolation::Linear).unwrap(), 2.0);
+        assert_close(evaluate(&KEYS, 15.0, Interpolation::Smooth).unwrap(), 2.0);
+        assert!(evaluate(&KEYS, 11.0, Interpolation::Smooth).unwrap() < 1.2);
+    }
+
+    #[test]
+    fn is_empty_without_keys() {
+        assert_eq!(evaluate::<f64>(&[], 5.0, Interpolation::Linear), None);
+        assert_eq!(
+            evaluate(&[(3.0, 2.0)], 5.0, Interpolation::CatmullRom),
+            Some(2.0)
+        );
+    }
+
+    #[test]
+    fn later_keyframes_on_the_same_frame_win() {
+        let keyframes = [
+            CameraKeyframe {
+                frame: 0,
+                vfov: Some(20.0),
+                ..Default::default()
+            },
+            CameraKeyframe {
+                frame: 0,
+                vfov: Some(30.0),
+                ..Default::default()
+            },
+            CameraKeyframe {
+                frame: 5,
+                ..Default::default()
+            },
+        ];
+        assert_eq!(channel_keys(&keyframes, |key| key.vfov), vec![(0.0, 30.0)]);
+    }
+
+    // part of `v` around the orbit axis
+    fn around_vup(config: &SceneConfig, v: Vec3) -> Vec3 {
+        let axis = config.vup.unit_vector();
+        v - axis * axis.dot(&v)
+    }
+
+    #[test]
+    fn partial_orbits_end_on_the_last_frame() {
+        let config = SceneConfig::default();
+        let orbit = CameraAnimation::orbit(&config, 90.0, 5);
+        assert_eq!(orbit.keyframes[0].lookfrom, Some(config.lookfrom));
+        let arm = around_vup(&config, config.lookfrom - config.lookat);
+        let last = around_vup(
+            &config,
+            orbit.keyframes[4].lookfrom.unwrap() - config.lookat,
+        );
+        assert_close(last.dot(&arm), 0.0);
+        assert_close(last.length(), arm.length());
+    }
+
+    #[test]
+    fn whole_orbits_stop_a_step_short() {
+        let config = SceneConfig::default();
+        let orbit = CameraAnimation::orbit(&config, 360.0, 4);
+        let arm = around_vup(&config, config.lookfrom - config.lookat);
+        let last = around_vup(
+            &config,
+            orbit.keyframes[3].lookfrom.unwrap() - config.lookat,
+        );
+        // a quarter turn before the start
+        assert_close(last.dot(&arm), 0.0);
+        assert!(config.vup.cross(&arm).dot(&last) < 0.0);
+    }
+
+    #[test]
+    fn dolly_zoom_ends_at_the_target() {
+        let config = SceneConfig::default();
+        let to = Point3::new(6.0, 1.0, 1.5);
+        let dolly = CameraAnimation::dolly_zoom(&config, to, 3);
+        let (first, last) = (dolly.keyframes[0], dolly.keyframes[2]);
+        assert_eq!(first.lookfrom, Some(config.lookfrom));
+        assert_close(first.vfov.unwrap(), config.vfov);
+        assert_eq!(last.lookfrom, Some(to));
+        assert_close(last.dist_to_focus.unwrap(), (config.lookat - to).length());
+    }
+}
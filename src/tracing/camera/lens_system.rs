@@ -0,0 +1,565 @@
+use std::path::PathBuf;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{rng, Point3, Ray, SceneConfig, Vec3};
+
+use super::{basis, Camera};
+
+/// Replaces the thin lens of the perspective camera with a stack of real
+/// lens elements, which brings their distortion, vignetting and aberrations.
+///
+/// ```toml
+/// [config.lens]
+/// file = "dgauss.50mm.dat"
+/// aperture_diameter = 10.0
+/// ```
+///
+/// The field of view follows from the lens and `sensor_diagonal`, `vfov` and
+/// `aperature` are unused. The lens focuses at `dist_to_focus`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LensSystem {
+    /// Lens table with a line of radius, thickness, IOR and aperture diameter
+    /// per surface, as in published prescriptions. Scene files resolve it
+    /// relative to themselves and the loader turns it into `elements`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+    /// Surfaces from the front of the lens to the back.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub elements: Vec<LensElement>,
+    /// Opening of the aperture stop in millimeters, at most the one in the
+    /// table, which is used when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aperture_diameter: Option<f64>,
+    /// Diagonal of the sensor in millimeters, 43.27 for a 36x24 mm full frame
+    /// one by default.
+    #[serde(default = "full_frame_diagonal")]
+    pub sensor_diagonal: f64,
+    /// Length of one scene unit in meters.
+    #[serde(default = "one_meter")]
+    pub scene_unit: f64,
+}
+
+fn full_frame_diagonal() -> f64 {
+    43.27
+}
+
+fn one_meter() -> f64 {
+    1.0
+}
+
+/// One surface of a lens table, lengths in millimeters.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LensElement {
+    /// Radius of curvature, positive when the center is behind the surface.
+    /// 0 marks the aperture stop.
+    pub radius: f64,
+    /// Distance to the next surface along the axis.
+    pub thickness: f64,
+    /// Refractive index of the medium behind the surface, 0 or 1 for air.
+    pub ior: f64,
+    /// Diameter of the surface.
+    pub aperture: f64,
+}
+
+impl LensElement {
+    /// Parses a lens table with four numbers per line, `#` starts a comment.
+    pub fn parse_table(source: &str) -> Result<Vec<LensElement>, String> {
+        let mut elements = Vec::new();
+        for (ix, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let values: Vec<f64> = line
+                .split_whitespace()
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map_err(|err| format!("line {}: {}", ix + 1, err))?;
+            let [radius, thickness, ior, aperture] = values[..] else {
+                return Err(format!(
+                    "line {}: expected radius, thickness, IOR and aperture, got {} values",
+                    ix + 1,
+                    values.len()
+                ));
+            };
+            elements.push(LensElement {
+                radius,
+                thickness,
+                ior,
+                aperture,
+            });
+        }
+        Ok(elements)
+    }
+}
+
+// A lens surface in meters, looking from the film along -z.
+#[derive(Debug, Copy, Clone)]
+struct Interface {
+    radius: f64,
+    thickness: f64,
+    // 1 for air
+    eta: f64,
+    aperture_radius: f64,
+}
+
+// film radius is split into this many rings, each with its own exit pupil
+const PUPIL_BINS: usize = 32;
+// grid points per side searched on the rear element for every ring
+const PUPIL_SAMPLES: usize = 64;
+
+#[derive(Debug, Copy, Clone)]
+struct Bounds {
+    min: (f64, f64),
+    max: (f64, f64),
+}
+
+impl Bounds {
+    fn area(&self) -> f64 {
+        (self.max.0 - self.min.0).max(0.0) * (self.max.1 - self.min.1).max(0.0)
+    }
+}
+
+/// Camera tracing rays from the film through a `LensSystem`, like pbrt's
+/// realistic camera.
+///
+/// Points on the rear element are sampled within the exit pupil of the
+/// film ring they come from. Rays blocked by the lens return `None`, and
+/// the cos⁴ falloff is applied by randomly dropping rays, so vignetting
+/// darkens the image without weighting samples.
+#[derive(Debug, Clone)]
+pub struct RealisticCamera {
+    m_origin: Point3,
+    m_cu: Vec3,
+    m_cv: Vec3,
+    m_cw: Vec3,
+    m_interfaces: Vec<Interface>,
+    m_film_width: f64,
+    m_film_height: f64,
+    m_scene_unit: f64,
+    m_pupils: Vec<Option<Bounds>>,
+    m_max_pupil_area: f64,
+}
+
+impl RealisticCamera {
+    /// Falls back to the spacing of the table when the lens can't focus at
+    /// `dist_to_focus`.
+    pub fn new(config: &SceneConfig, lens: &LensSystem) -> RealisticCamera {
+        let (cu, cv, cw) = basis(config.lookfrom, config.lookat, config.vup);
+        let diagonal = lens.sensor_diagonal / 1000.0;
+        let aspect_ratio = config.aspect_ratio;
+        let mut camera = RealisticCamera {
+            m_origin: config.lookfrom,
+            m_cu: cu,
+            m_cv: cv,
+            m_cw: cw,
+            m_interfaces: interfaces(lens),
+            m_film_width: diagonal * aspect_ratio / (aspect_ratio * aspect_ratio + 1.0).sqrt(),
+            m_film_height: diagonal / (aspect_ratio * aspect_ratio + 1.0).sqrt(),
+            m_scene_unit: lens.scene_unit,
+            m_pupils: Vec::new(),
+            m_max_pupil_area: 0.0,
+        };
+        if let Some(thickness) = camera.focus_thickness(config.dist_to_focus * lens.scene_unit) {
+            if let Some(last) = camera.m_interfaces.last_mut() {
+                last.thickness = thickness;
+            }
+        }
+        camera.m_pupils = (0..PUPIL_BINS)
+            .map(|bin| {
+                let radius = camera.film_diagonal() / 2.0;
+                camera.pupil_bounds(
+                    bin as f64 / PUPIL_BINS as f64 * radius,
+                    (bin + 1) as f64 / PUPIL_BINS as f64 * radius,
+                )
+            })
+            .collect();
+        camera.m_max_pupil_area = camera
+            .m_pupils
+            .iter()
+            .flatten()
+            .map(Bounds::area)
+            .fold(0.0, f64::max);
+        camera
+    }
+
+    /// `true` when the lens can focus at `dist_to_focus` of `config` and lets
+    /// light through to the film.
+    pub fn can_focus(config: &SceneConfig, lens: &LensSystem) -> bool {
+        let camera = RealisticCamera::new(config, lens);
+        camera
+            .focus_thickness(config.dist_to_focus * lens.scene_unit)
+            .is_some()
+            && camera.m_max_pupil_area > 0.0
+    }
+
+    fn film_diagonal(&self) -> f64 {
+        self.m_film_width.hypot(self.m_film_height)
+    }
+
+    fn rear_z(&self) -> f64 {
+        self.m_interfaces.last().map_or(0.0, |last| last.thickness)
+    }
+
+    fn front_z(&self) -> f64 {
+        self.m_interfaces.iter().map(|i| i.thickness).sum()
+    }
+
+    // Distance from the rear element to the film that focuses at `distance`
+    // meters, from the thick lens approximation.
+    fn focus_thickness(&self, distance: f64) -> Option<f64> {
+        let x = 0.001 * self.film_diagonal();
+        let scene_ray = (
+            Point3::new(x, 0.0, self.front_z() + 1.0),
+            Vec3::new(0.0, 0.0, -1.0),
+        );
+        let (pz0, fz0) = cardinal_points(scene_ray, self.trace_from_scene(scene_ray)?);
+        let film_ray = (
+            Point3::new(x, 0.0, self.rear_z() - 1.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        );
+        let (pz1, _) = cardinal_points(film_ray, self.trace_from_film(film_ray)?);
+
+        let f = fz0 - pz0;
+        let z = -distance;
+        let c = (pz1 - z - pz0) * (pz1 - z - 4.0 * f - pz0);
+        let delta = 0.5 * (pz1 - z + pz0 - c.sqrt());
+        let thickness = self.rear_z() + delta;
+        (thickness.is_finite() && thickness > 0.0).then_some(thickness)
+    }
+
+    // Box on the rear element plane through which light from film radii
+    // `r0` to `r1` on the x axis leaves the lens.
+    fn pupil_bounds(&self, r0: f64, r1: f64) -> Option<Bounds> {
+        let rear_z = self.rear_z();
+        let extent = 1.5 * self.m_interfaces.last()?.aperture_radius;
+        let step = 2.0 * extent / PUPIL_SAMPLES as f64;
+        let mut bounds: Option<Bounds> = None;
+        for ix in 0..PUPIL_SAMPLES * PUPIL_SAMPLES {
+            let film = Point3::new(
+                r0 + (r1 - r0) * ix as f64 / (PUPIL_SAMPLES * PUPIL_SAMPLES) as f64,
+                0.0,
+                0.0,
+            );
+            let x = -extent + ((ix % PUPIL_SAMPLES) as f64 + 0.5) * step;
+            let y = -extent + ((ix / PUPIL_SAMPLES) as f64 + 0.5) * step;
+            let rear = Point3::new(x, y, rear_z);
+            if self.trace_from_film((film, rear - film)).is_some() {
+                bounds = Some(match bounds {
+                    Some(b) => Bounds {
+                        min: (b.min.0.min(x), b.min.1.min(y)),
+                        max: (b.max.0.max(x), b.max.1.max(y)),
+                    },
+                    None => Bounds {
+                        min: (x, y),
+                        max: (x, y),
+                    },
+                });
+            }
+        }
+        // grow by a grid cell so the edges aren't cut off
+        bounds.map(|b| Bounds {
+            min: (b.min.0 - step, b.min.1 - step),
+            max: (b.max.0 + step, b.max.1 + step),
+        })
+    }
+
+    // Camera space, film at z = 0 and the lens towards +z.
+    fn trace_from_film(&self, (origin, direction): (Point3, Vec3)) -> Option<(Point3, Vec3)> {
+        let mut o = flip(origin);
+        let mut d = flip(direction);
+        let mut element_z = 0.0;
+        for (ix, interface) in self.m_interfaces.iter().enumerate().rev() {
+            element_z -= interface.thickness;
+            let (t, normal) = match interface.radius == 0.0 {
+                true => {
+                    if d.z() >= 0.0 {
+                        return None;
+                    }
+                    ((element_z - o.z()) / d.z(), None)
+                }
+                false => {
+                    let (t, n) =
+                        intersect_spherical(interface.radius, element_z + interface.radius, o, d)?;
+                    (t, Some(n))
+                }
+            };
+            let hit = o + t * d;
+            if hit.x() * hit.x() + hit.y() * hit.y() > interface.aperture_radius.powi(2) {
+                return None;
+            }
+            o = hit;
+            if let Some(normal) = normal {
+                let eta_t = match ix {
+                    0 => 1.0,
+                    _ => self.m_interfaces[ix - 1].eta,
+                };
+                d = refract(-d.unit_vector(), normal, interface.eta / eta_t)?;
+            }
+        }
+        Some((flip(o), flip(d)))
+    }
+
+    fn trace_from_scene(&self, (origin, direction): (Point3, Vec3)) -> Option<(Point3, Vec3)> {
+        let mut o = flip(origin);
+        let mut d = flip(direction);
+        let mut element_z = -self.front_z();
+        for (ix, interface) in self.m_interfaces.iter().enumerate() {
+            let (t, normal) = match interface.radius == 0.0 {
+                true => ((element_z - o.z()) / d.z(), None),
+                false => {
+                    let (t, n) =
+                        intersect_spherical(interface.radius, element_z + interface.radius, o, d)?;
+                    (t, Some(n))
+                }
+            };
+            let hit = o + t * d;
+            if hit.x() * hit.x() + hit.y() * hit.y() > interface.aperture_radius.powi(2) {
+                return None;
+            }
+            o = hit;
+            if let Some(normal) = normal {
+                let eta_i = match ix {
+                    0 => 1.0,
+                    _ => self.m_interfaces[ix - 1].eta,
+                };
+                d = refract(-d.unit_vector(), normal, eta_i / interface.eta)?;
+            }
+            element_z += interface.thickness;
+        }
+        Some((flip(o), flip(d)))
+    }
+
+    // Traces the ray from the film at `u`, `v` through the point `pick`
+    // chooses on the exit pupil, with the chance to keep it for vignetting.
+    fn trace_pupil_point(
+        &self,
+        u: f64,
+        v: f64,
+        pick: impl FnOnce(&Bounds) -> (f64, f64),
+    ) -> Option<(Ray, f64)> {
+        // the lens turns the image upside down
+        let film = Point3::new(
+            -(u - 0.5) * self.m_film_width,
+            -(v - 0.5) * self.m_film_height,
+            0.0,
+        );
+        let radius = film.x().hypot(film.y());
+        let bin = ((radius / (self.film_diagonal() / 2.0) * PUPIL_BINS as f64) as usize)
+            .min(PUPIL_BINS - 1);
+        let pupil = self.m_pupils[bin]?;
+
+        // pupils are found along +x, turn them to the film point
+        let (x, y) = pick(&pupil);
+        let (sin, cos) = match radius > 0.0 {
+            true => (film.y() / radius, film.x() / radius),
+            false => (0.0, 1.0),
+        };
+        let rear = Point3::new(cos * x - sin * y, sin * x + cos * y, self.rear_z());
+        let cos_theta = (rear - film).unit_vector().z();
+        let (origin, direction) = self.trace_from_film((film, rear - film))?;
+
+        let weight = pupil.area() / self.m_max_pupil_area * cos_theta.powi(4);
+        let to_world = |v: Vec3| v.x() * self.m_cu + v.y() * self.m_cv - v.z() * self.m_cw;
+        let ray = Ray::new(
+            self.m_origin + to_world(origin) / self.m_scene_unit,
+            to_world(direction),
+        );
+        Some((ray, weight))
+    }
+}
+
+impl Camera for RealisticCamera {
+    fn get_ray(&self, u: f64, v: f64) -> Option<Ray> {
+        let mut rng = rng();
+        let (ray, weight) = self.trace_pupil_point(u, v, |pupil| {
+            (
+                pupil.min.0 + rng.gen::<f64>() * (pupil.max.0 - pupil.min.0),
+                pupil.min.1 + rng.gen::<f64>() * (pupil.max.1 - pupil.min.1),
+            )
+        })?;
+        if rng.gen::<f64>() >= weight {
+            return None;
+        }
+        Some(ray)
+    }
+
+    fn center_ray(&self, u: f64, v: f64) -> Option<Ray> {
+        let (ray, _) = self.trace_pupil_point(u, v, |pupil| {
+            (
+                (pupil.min.0 + pupil.max.0) / 2.0,
+                (pupil.min.1 + pupil.max.1) / 2.0,
+            )
+        })?;
+        Some(ray)
+    }
+}
+
+fn interfaces(lens: &LensSystem) -> Vec<Interface> {
+    lens.elements
+        .iter()
+        .map(|element| {
+            let mut aperture = element.aperture;
+            if element.radius == 0.0 {
+                if let Some(diameter) = lens.aperture_diameter {
+                    aperture = aperture.min(diameter);
+                }
+            }
+            Interface {
+                radius: element.radius / 1000.0,
+                thickness: element.thickness / 1000.0,
+                eta: if element.ior == 0.0 { 1.0 } else { element.ior },
+                aperture_radius: aperture / 2000.0,
+            }
+        })
+        .collect()
+}
+
+// between lens space, where the lens looks along -z, and camera space
+fn flip(v: Vec3) -> Vec3 {
+    Vec3::new(v.x(), v.y(), -v.z())
+}
+
+// Principal plane and focal point on the axis for a ray parallel to it.
+fn cardinal_points(input: (Point3, Vec3), output: (Point3, Vec3)) -> (f64, f64) {
+    let (o, d) = output;
+    let tf = -o.x() / d.x();
+    let fz = -(o + tf * d).z();
+    let tp = (input.0.x() - o.x()) / d.x();
+    let pz = -(o + tp * d).z();
+    (pz, fz)
+}
+
+fn intersect_spherical(radius: f64, z_center: f64, o: Point3, d: Vec3) -> Option<(f64, Vec3)> {
+    let oc = o - Vec3::new(0.0, 0.0, z_center);
+    let a = d.length_squared();
+    let b = 2.0 * d.dot(&oc);
+    let c = oc.length_squared() - radius * radius;
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    let q = match b < 0.0 {
+        true => -0.5 * (b - root),
+        false => -0.5 * (b + root),
+    };
+    let (t0, t1) = (q / a, c / q);
+    let (near, far) = (t0.min(t1), t0.max(t1));
+    // the side of the sphere the lens surface is on
+    let t = match (d.z() > 0.0) ^ (radius < 0.0) {
+        true => near,
+        false => far,
+    };
+    if t.is_nan() || t < 0.0 {
+        return None;
+    }
+    let normal = (oc + t * d).unit_vector();
+    match normal.dot(&-d) < 0.0 {
+        true => Some((t, -normal)),
+        false => Some((t, normal)),
+    }
+}
+
+fn refract(wi: Vec3, normal: Vec3, eta: f64) -> Option<Vec3> {
+    let cos_i = normal.dot(&wi);
+    let sin2_i = (1.0 - cos_i * cos_i).max(0.0);
+    let sin2_t = eta * eta * sin2_i;
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(eta * -wi + (eta * cos_i - cos_t) * normal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOUBLE_GAUSS: &str = "# D-GAUSS F/2 22deg HFOV, scaled to 50 mm
+# radius  sep  n  aperture
+29.475  3.76   1.67   25.2
+84.83   0.12   1      25.2
+19.275  4.025  1.67   23
+40.77   3.275  1.699  23
+12.75   5.705  1      18
+0       4.5    0      17.1
+-14.495 1.18   1.603  17
+40.77   6.065  1.658  20
+-20.385 0.19   1      20
+437.065 3.22   1.717  20
+-39.73  0      1      20
+";
+
+    fn double_gauss() -> LensSystem {
+        LensSystem {
+            file: None,
+            elements: LensElement::parse_table(DOUBLE_GAUSS).unwrap(),
+            aperture_diameter: None,
+            sensor_diagonal: full_frame_diagonal(),
+            scene_unit: one_meter(),
+        }
+    }
+
+    #[test]
+    fn parses_tables_with_comments() {
+        let table =
+            "# radius thickness ior aperture\n\n29.475 3.76 1.67 25.2 # front\n0 4.5 0 17.1\n";
+        let elements = LensElement::parse_table(table).unwrap();
+        assert_eq!(
+            elements,
+            vec![
+                LensElement {
+                    radius: 29.475,
+                    thickness: 3.76,
+                    ior: 1.67,
+                    aperture: 25.2,
+                },
+                LensElement {
+                    radius: 0.0,
+                    thickness: 4.5,
+                    ior: 0.0,
+                    aperture: 17.1,
+                },
+            ]
+        );
+        assert_eq!(LensElement::parse_table(DOUBLE_GAUSS).unwrap().len(), 11);
+    }
+
+    #[test]
+    fn rejects_malformed_tables() {
+        assert_eq!(
+            LensElement::parse_table("# lens\n29.475 3.76 1.67\n"),
+            Err("line 2: expected radius, thickness, IOR and aperture, got 3 values".to_string())
+        );
+        let error = LensElement::parse_table("1 2 3 4\n1 2 x 4\n").unwrap_err();
+        assert!(error.starts_with("line 2: "));
+    }
+
+    #[test]
+    fn defaults_to_a_full_frame_sensor() {
+        let lens: LensSystem = toml::from_str("file = \"dgauss.dat\"").unwrap();
+        assert_eq!(lens.sensor_diagonal, 43.27);
+        assert_eq!(lens.scene_unit, 1.0);
+    }
+
+    #[test]
+    fn center_rays_are_the_same_every_time() {
+        let config = SceneConfig::default();
+        let camera = RealisticCamera::new(&config, &double_gauss());
+        let ray = camera.center_ray(0.3, 0.6).unwrap();
+        for _ in 0..100 {
+            let again = camera.center_ray(0.3, 0.6).unwrap();
+            assert_eq!(again.origin(), ray.origin());
+            assert_eq!(again.direction(), ray.direction());
+        }
+        // through the middle of the image the lens looks straight ahead
+        let ray = camera.center_ray(0.5, 0.5).unwrap();
+        let ahead = (config.lookat - config.lookfrom).unit_vector();
+        assert!(ray.direction().unit_vector().dot(&ahead) > 0.9999);
+    }
+}
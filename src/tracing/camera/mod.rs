@@ -0,0 +1,127 @@
+pub mod aperture;
+pub use aperture::*;
+pub mod focus;
+pub use focus::*;
+pub mod keyframes;
+pub use keyframes::*;
+pub mod lens_system;
+pub use lens_system::*;
+pub mod perspective;
+pub use perspective::*;
+pub mod orthographic;
+pub use orthographic::*;
+pub mod physical;
+pub use physical::*;
+pub mod panoramic;
+pub use panoramic::*;
+pub mod stereo;
+pub use stereo::*;
+pub mod tilt_shift;
+pub use tilt_shift::*;
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Point3, Ray, SceneConfig, Vec3};
+
+/// Turns positions on the image into primary rays.
+pub trait Camera: Send + Sync {
+    /// `u` and `v` run from 0 to 1 across the image, starting at the bottom
+    /// left corner. `None` where the camera doesn't see anything, like the
+    /// corners outside a fisheye's image circle.
+    fn get_ray(&self, u: f64, v: f64) -> Option<Ray>;
+
+    /// The ray through the center of the lens, the same on every call, for
+    /// finding what's under a pixel.
+    fn center_ray(&self, u: f64, v: f64) -> Option<Ray> {
+        self.get_ray(u, v)
+    }
+}
+
+/// How the scene is projected onto the image.
+///
+/// ```toml
+/// [config]
+/// projection = { type = "orthographic", view_height = 4.0 }
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Projection {
+    /// Thin lens camera using `vfov`, `aperature` and `dist_to_focus`.
+    #[default]
+    Perspective,
+    /// Parallel rays along the view direction, `view_height` is the height of
+    /// the visible area in world units.
+    Orthographic { view_height: f64 },
+    /// Latitude-longitude panorama of the full sphere around `lookfrom`,
+    /// with `vup` as the pole and `lookat` in the center of the image.
+    Equirectangular,
+    /// Circular fisheye looking at `lookat`, `fov` in degrees spans the
+    /// image height.
+    Fisheye {
+        fov: f64,
+        #[serde(default)]
+        mapping: FisheyeMapping,
+    },
+}
+
+/// The camera `config` describes, with the field of view and lens size of
+/// its physical camera if it has one.
+pub fn build_camera(config: &SceneConfig) -> Arc<dyn Camera> {
+    let mut config = config.clone();
+    if let Some(physical) = config.physical {
+        physical.apply(&mut config);
+    }
+    match config.stereo {
+        Some(stereo) => Arc::new(StereoCamera::new(&config, stereo)),
+        None => build_mono_camera(&config),
+    }
+}
+
+fn build_mono_camera(config: &SceneConfig) -> Arc<dyn Camera> {
+    match config.projection {
+        Projection::Perspective => match &config.lens {
+            Some(lens) => Arc::new(RealisticCamera::new(config, lens)),
+            None => Arc::new(PerspectiveCamera::from(config)),
+        },
+        Projection::Orthographic { view_height } => Arc::new(OrthographicCamera::new(
+            config.lookfrom,
+            config.lookat,
+            config.vup,
+            view_height,
+            config.aspect_ratio,
+        )),
+        Projection::Equirectangular => Arc::new(EquirectangularCamera::new(
+            config.lookfrom,
+            config.lookat,
+            config.vup,
+        )),
+        Projection::Fisheye { fov, mapping } => Arc::new(FisheyeCamera::new(
+            config.lookfrom,
+            config.lookat,
+            config.vup,
+            fov,
+            mapping,
+            config.aspect_ratio,
+        )),
+    }
+}
+
+// Orthonormal camera basis, `w` points backwards from the view direction.
+fn basis(lookfrom: Point3, lookat: Point3, vup: Vec3) -> (Vec3, Vec3, Vec3) {
+    let cw = (lookfrom - lookat).unit_vector();
+    let cu = vup.cross(&cw).unit_vector();
+    let cv = cw.cross(&cu);
+    (cu, cv, cw)
+}
+
+pub fn clamp(x: f64, min: f64, max: f64) -> f64 {
+    if x < min {
+        min
+    } else if x > max {
+        max
+    } else {
+        x
+    }
+}
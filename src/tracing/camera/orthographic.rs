@@ -0,0 +1,46 @@
+use crate::{Point3, Ray, Vec3};
+
+use super::{basis, Camera};
+
+/// Camera without perspective, parallel lines stay parallel in the image.
+///
+/// Rays start on the plane through `lookfrom`, anything behind it is not
+/// seen.
+#[derive(Debug, Clone)]
+pub struct OrthographicCamera {
+    m_lower_left_corner: Point3,
+    m_horizontal: Vec3,
+    m_vertical: Vec3,
+    m_direction: Vec3,
+}
+
+impl OrthographicCamera {
+    pub fn new(
+        lookfrom: Point3,
+        lookat: Point3,
+        vup: Vec3,
+        view_height: f64,
+        aspect_ratio: f64,
+    ) -> OrthographicCamera {
+        let (cu, cv, cw) = basis(lookfrom, lookat, vup);
+
+        let h = view_height * aspect_ratio * cu;
+        let v = view_height * cv;
+
+        OrthographicCamera {
+            m_lower_left_corner: lookfrom - h / 2.0 - v / 2.0,
+            m_horizontal: h,
+            m_vertical: v,
+            m_direction: -cw,
+        }
+    }
+}
+
+impl Camera for OrthographicCamera {
+    fn get_ray(&self, u: f64, v: f64) -> Option<Ray> {
+        Some(Ray::new(
+            self.m_lower_left_corner + u * self.m_horizontal + v * self.m_vertical,
+            self.m_direction,
+        ))
+    }
+}
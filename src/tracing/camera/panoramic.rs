@@ -0,0 +1,161 @@
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Point3, Ray, Vec3};
+
+use super::{basis, Camera};
+
+/// How a fisheye maps the angle from the view direction to the distance
+/// from the image center.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FisheyeMapping {
+    /// Distance proportional to the angle, the usual dome master projection.
+    #[default]
+    Equidistant,
+    /// Equal areas on the image cover equal solid angles, like most real
+    /// fisheye lenses.
+    Equisolid,
+}
+
+/// Pole-aligned basis for panoramas: `forward` is the view direction made
+/// perpendicular to `up`, so a tilted `lookat` doesn't tilt the horizon.
+fn pole_basis(lookfrom: Point3, lookat: Point3, vup: Vec3) -> (Vec3, Vec3, Vec3) {
+    let up = vup.unit_vector();
+    let view = lookat - lookfrom;
+    let mut forward = view - view.dot(&up) * up;
+    if forward.near_zero() {
+        // looking straight along the pole, any horizontal direction will do
+        let axis = match up.x().abs() < 0.9 {
+            true => Vec3::new(1.0, 0.0, 0.0),
+            false => Vec3::new(0.0, 0.0, 1.0),
+        };
+        forward = axis - axis.dot(&up) * up;
+    }
+    let forward = forward.unit_vector();
+    (forward.cross(&up), up, forward)
+}
+
+/// Latitude-longitude panorama, the image spans 360 degrees horizontally and
+/// 180 degrees vertically whatever its aspect ratio.
+#[derive(Debug, Clone)]
+pub struct EquirectangularCamera {
+    m_origin: Point3,
+    m_right: Vec3,
+    m_up: Vec3,
+    m_forward: Vec3,
+    // sideways offset of the eye for omni-directional stereo, 0 for mono
+    m_eye_offset: f64,
+    m_convergence: f64,
+}
+
+impl EquirectangularCamera {
+    pub fn new(lookfrom: Point3, lookat: Point3, vup: Vec3) -> EquirectangularCamera {
+        let (right, up, forward) = pole_basis(lookfrom, lookat, vup);
+        EquirectangularCamera {
+            m_origin: lookfrom,
+            m_right: right,
+            m_up: up,
+            m_forward: forward,
+            m_eye_offset: 0.0,
+            m_convergence: f64::INFINITY,
+        }
+    }
+
+    /// One eye of an omni-directional stereo pair: rays start `offset` to
+    /// the right (left when negative) of `lookfrom`, perpendicular to their
+    /// heading, and converge at `convergence` from `lookfrom`.
+    pub fn with_eye(self, offset: f64, convergence: f64) -> EquirectangularCamera {
+        EquirectangularCamera {
+            m_eye_offset: offset,
+            m_convergence: convergence,
+            ..self
+        }
+    }
+
+    /// Unit direction for image position `u`, `v`.
+    pub fn direction(&self, u: f64, v: f64) -> Vec3 {
+        let longitude = (u - 0.5) * 2.0 * PI;
+        let latitude = (v - 0.5) * PI;
+        latitude.cos() * (longitude.cos() * self.m_forward + longitude.sin() * self.m_right)
+            + latitude.sin() * self.m_up
+    }
+}
+
+impl Camera for EquirectangularCamera {
+    fn get_ray(&self, u: f64, v: f64) -> Option<Ray> {
+        let direction = self.direction(u, v);
+        if self.m_eye_offset == 0.0 {
+            return Some(Ray::new(self.m_origin, direction));
+        }
+        let longitude = (u - 0.5) * 2.0 * PI;
+        let right = longitude.cos() * self.m_right - longitude.sin() * self.m_forward;
+        let origin = self.m_origin + self.m_eye_offset * right;
+        let direction = match self.m_convergence.is_finite() {
+            true => self.m_origin + self.m_convergence * direction - origin,
+            false => direction,
+        };
+        Some(Ray::new(origin, direction))
+    }
+}
+
+/// Circular fisheye, the image circle touches the top and bottom of the
+/// image and nothing is seen outside it.
+#[derive(Debug, Clone)]
+pub struct FisheyeCamera {
+    m_origin: Point3,
+    m_cu: Vec3,
+    m_cv: Vec3,
+    m_cw: Vec3,
+    m_half_fov: f64,
+    m_mapping: FisheyeMapping,
+    m_aspect_ratio: f64,
+}
+
+impl FisheyeCamera {
+    pub fn new(
+        lookfrom: Point3,
+        lookat: Point3,
+        vup: Vec3,
+        fov: f64,
+        mapping: FisheyeMapping,
+        aspect_ratio: f64,
+    ) -> FisheyeCamera {
+        let (cu, cv, cw) = basis(lookfrom, lookat, vup);
+        FisheyeCamera {
+            m_origin: lookfrom,
+            m_cu: cu,
+            m_cv: cv,
+            m_cw: cw,
+            // beyond 360 degrees the image would wrap around
+            m_half_fov: fov.clamp(0.0, 360.0).to_radians() / 2.0,
+            m_mapping: mapping,
+            m_aspect_ratio: aspect_ratio,
+        }
+    }
+}
+
+impl Camera for FisheyeCamera {
+    fn get_ray(&self, u: f64, v: f64) -> Option<Ray> {
+        // unit radius at the top and bottom edges
+        let x = (2.0 * u - 1.0) * self.m_aspect_ratio;
+        let y = 2.0 * v - 1.0;
+        let radius = (x * x + y * y).sqrt();
+        if radius > 1.0 {
+            return None;
+        }
+        let theta = match self.m_mapping {
+            FisheyeMapping::Equidistant => radius * self.m_half_fov,
+            FisheyeMapping::Equisolid => {
+                2.0 * (radius * (self.m_half_fov / 2.0).sin())
+                    .clamp(-1.0, 1.0)
+                    .asin()
+            }
+        };
+        let phi = y.atan2(x);
+        let direction =
+            theta.sin() * (phi.cos() * self.m_cu + phi.sin() * self.m_cv) - theta.cos() * self.m_cw;
+        Some(Ray::new(self.m_origin, direction))
+    }
+}
@@ -0,0 +1,128 @@
+use crate::{Point3, Ray, SceneConfig, Vec3};
+
+use super::{basis, Bokeh, Camera, TiltShift};
+
+#[derive(Debug, Clone)]
+pub struct PerspectiveCamera {
+    m_origin: Point3,
+    m_lower_left_corner: Point3,
+    m_horizontal: Vec3,
+    m_vertical: Vec3,
+    m_cu: Vec3,
+    m_cv: Vec3,
+    m_lens_radius: f64,
+    m_bokeh: Bokeh,
+    m_aspect_ratio: f64,
+    // point and normal of a tilted plane of focus, `None` when it faces the
+    // camera
+    m_focus_plane: Option<(Point3, Vec3)>,
+    m_cw: Vec3,
+    m_focus_dist: f64,
+}
+
+impl PerspectiveCamera {
+    pub fn new(
+        lookfrom: Point3,
+        lookat: Point3,
+        vup: Vec3,
+        vfov: f64,
+        aspect_ratio: f64,
+        aperature: f64,
+        focus_dist: f64,
+    ) -> PerspectiveCamera {
+        // vertical fov
+        let theta = std::f64::consts::PI / 180.0 * vfov;
+        let viewport_height = 2.0 * (theta / 2.0).tan();
+        let viewport_width = aspect_ratio * viewport_height;
+
+        let (cu, cv, cw) = basis(lookfrom, lookat, vup);
+
+        let h = focus_dist * viewport_width * cu;
+        let v = focus_dist * viewport_height * cv;
+
+        let llc = lookfrom - h / 2.0 - v / 2.0 - focus_dist * cw;
+
+        PerspectiveCamera {
+            m_origin: lookfrom,
+            m_horizontal: h,
+            m_vertical: v,
+            m_lower_left_corner: llc,
+            m_cu: cu,
+            m_cv: cv,
+            m_lens_radius: aperature / 2.0,
+            m_bokeh: Bokeh::default(),
+            m_aspect_ratio: aspect_ratio,
+            m_focus_plane: None,
+            m_cw: cw,
+            m_focus_dist: focus_dist,
+        }
+    }
+
+    pub fn from(config: &SceneConfig) -> PerspectiveCamera {
+        PerspectiveCamera::new(
+            config.lookfrom,
+            config.lookat,
+            config.vup,
+            config.vfov,
+            config.aspect_ratio,
+            config.aperature,
+            config.dist_to_focus,
+        )
+        .with_bokeh(config.bokeh.clone())
+        .with_tilt_shift(config.tilt_shift)
+    }
+
+    /// Replaces the round lens opening.
+    pub fn with_bokeh(self, bokeh: Bokeh) -> PerspectiveCamera {
+        PerspectiveCamera {
+            m_bokeh: bokeh,
+            ..self
+        }
+    }
+
+    /// Applies the lens movements of `tilt_shift`.
+    pub fn with_tilt_shift(mut self, tilt_shift: TiltShift) -> PerspectiveCamera {
+        let [shift_x, shift_y] = tilt_shift.shift;
+        self.shift_window(shift_x * self.m_horizontal + shift_y * self.m_vertical);
+        if tilt_shift.is_tilted() {
+            let [tilt_x, tilt_y] = tilt_shift.tilt.map(f64::to_radians);
+            let top = tilt_x.cos() * self.m_cv - tilt_x.sin() * self.m_cw;
+            let right = tilt_y.cos() * self.m_cu - tilt_y.sin() * self.m_cw;
+            let center = self.m_origin - self.m_focus_dist * self.m_cw;
+            self.m_focus_plane = Some((center, right.cross(&top)));
+        }
+        self
+    }
+
+    /// Moves the image window by `offset` at the focus distance while the
+    /// lens stays put, shifting the view without turning the camera.
+    pub fn shift_window(&mut self, offset: Vec3) {
+        self.m_lower_left_corner += offset;
+    }
+}
+
+impl Camera for PerspectiveCamera {
+    fn get_ray(&self, u: f64, v: f64) -> Option<Ray> {
+        let (x, y) = self.m_bokeh.sample(u, v, self.m_aspect_ratio);
+        let offset = self.m_lens_radius * (self.m_cu * x + self.m_cv * y);
+        let mut target = self.m_lower_left_corner + u * self.m_horizontal + v * self.m_vertical;
+        if let Some((center, normal)) = self.m_focus_plane {
+            // rays through the lens meet where the central one crosses the plane
+            let direction = target - self.m_origin;
+            let t = (center - self.m_origin).dot(&normal) / direction.dot(&normal);
+            if t.is_finite() && t > 0.0 {
+                target = self.m_origin + t * direction;
+            }
+        }
+
+        Some(Ray::new(
+            self.m_origin + offset,
+            target - self.m_origin - offset,
+        ))
+    }
+
+    fn center_ray(&self, u: f64, v: f64) -> Option<Ray> {
+        let target = self.m_lower_left_corner + u * self.m_horizontal + v * self.m_vertical;
+        Some(Ray::new(self.m_origin, target - self.m_origin))
+    }
+}
@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+
+use crate::SceneConfig;
+
+/// Camera settings in photographic units, `vfov`, `aperature` and the
+/// exposure are derived from them when the camera is built and the image
+/// tone mapped, whatever the config holds.
+///
+/// ```toml
+/// [config.physical]
+/// focal_length = 50.0
+/// f_number = 2.8
+/// shutter = 0.008
+/// iso = 400.0
+/// ```
+///
+/// Scene radiance is taken to be in cd/m², a sunlit scene of a few thousand
+/// cd/m² is well exposed at f/16, 1/100 s and ISO 100.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PhysicalCamera {
+    /// In millimeters.
+    pub focal_length: f64,
+    /// Sensor size in millimeters, a full frame 36x24 sensor by default. The
+    /// image is fitted inside it, so other aspect ratios crop the sensor.
+    #[serde(default = "full_frame_width")]
+    pub sensor_width: f64,
+    #[serde(default = "full_frame_height")]
+    pub sensor_height: f64,
+    /// Focal length divided by the aperture diameter.
+    pub f_number: f64,
+    /// Exposure time in seconds.
+    pub shutter: f64,
+    #[serde(default = "base_iso")]
+    pub iso: f64,
+    /// Length of one scene unit in meters, used to size the lens.
+    #[serde(default = "one_meter")]
+    pub scene_unit: f64,
+}
+
+fn full_frame_width() -> f64 {
+    36.0
+}
+
+fn full_frame_height() -> f64 {
+    24.0
+}
+
+fn base_iso() -> f64 {
+    100.0
+}
+
+fn one_meter() -> f64 {
+    1.0
+}
+
+impl PhysicalCamera {
+    /// `true` when every setting is positive, otherwise the derived values
+    /// are meaningless.
+    pub fn is_valid(&self) -> bool {
+        [
+            self.focal_length,
+            self.sensor_width,
+            self.sensor_height,
+            self.f_number,
+            self.shutter,
+            self.iso,
+            self.scene_unit,
+        ]
+        .iter()
+        .all(|value| *value > 0.0)
+    }
+
+    /// Vertical field of view in degrees for images of `aspect_ratio`.
+    pub fn vfov(&self, aspect_ratio: f64) -> f64 {
+        let height = self.sensor_height.min(self.sensor_width / aspect_ratio);
+        2.0 * (height / 2.0 / self.focal_length).atan().to_degrees()
+    }
+
+    /// Diameter of the aperture in scene units.
+    pub fn aperture_diameter(&self) -> f64 {
+        self.focal_length / self.f_number / 1000.0 / self.scene_unit
+    }
+
+    /// Exposure value at ISO 100 of the settings, higher for less light.
+    pub fn ev100(&self) -> f64 {
+        (self.f_number * self.f_number / self.shutter * 100.0 / self.iso).log2()
+    }
+
+    /// Exposure in stops to scale radiance by, so the brightest luminance
+    /// the sensor can record without saturating maps to 1.
+    pub fn exposure(&self) -> f64 {
+        -self.ev100() - 1.2_f64.log2()
+    }
+
+    /// Overwrites `vfov` and `aperature` of `config` with the derived ones.
+    pub fn apply(&self, config: &mut SceneConfig) {
+        config.vfov = self.vfov(config.aspect_ratio);
+        config.aperature = self.aperture_diameter();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_camera;
+
+    #[test]
+    fn cameras_use_the_derived_field_of_view() {
+        let physical = PhysicalCamera {
+            focal_length: 35.0,
+            sensor_width: 36.0,
+            sensor_height: 24.0,
+            f_number: 4.0,
+            shutter: 0.01,
+            iso: 100.0,
+            scene_unit: 1.0,
+        };
+        let stale = SceneConfig {
+            vfov: 90.0,
+            physical: Some(physical),
+            ..SceneConfig::default()
+        };
+        let derived = SceneConfig {
+            vfov: physical.vfov(stale.aspect_ratio),
+            physical: None,
+            ..SceneConfig::default()
+        };
+        let corner = |config: &SceneConfig| {
+            let ray = build_camera(config).center_ray(1.0, 1.0).unwrap();
+            ray.direction().unit_vector()
+        };
+        assert!((corner(&stale) - corner(&derived)).length() < 1e-12);
+    }
+}
@@ -0,0 +1,120 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Ray, SceneConfig};
+
+use super::{basis, build_mono_camera, Camera, EquirectangularCamera, Projection};
+
+/// Renders both eyes into one image.
+///
+/// ```toml
+/// [config.stereo]
+/// interocular = 0.065
+/// convergence = 3.0
+/// layout = "over_under"
+/// ```
+///
+/// Each eye gets half of the image, so side by side halves the width each
+/// eye sees, double `image_width` to keep it. An equirectangular projection
+/// becomes omni-directional stereo, with the eyes circling `lookfrom`.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Stereo {
+    /// Distance between the eyes, in scene units.
+    pub interocular: f64,
+    /// Distance from the camera at which both eyes see the same image, so
+    /// objects there appear at screen depth. Thin lens perspective eyes shift
+    /// their view to converge, the other projections and lens prescriptions
+    /// turn in.
+    pub convergence: f64,
+    #[serde(default)]
+    pub layout: StereoLayout,
+}
+
+/// Where the eyes go in the image, the left eye is always on the left or on
+/// top.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StereoLayout {
+    #[default]
+    SideBySide,
+    OverUnder,
+}
+
+pub struct StereoCamera {
+    m_left: Arc<dyn Camera>,
+    m_right: Arc<dyn Camera>,
+    m_layout: StereoLayout,
+}
+
+impl StereoCamera {
+    /// Cameras for both eyes of `config`, whose `projection` is used for each
+    /// eye.
+    pub fn new(config: &SceneConfig, stereo: Stereo) -> StereoCamera {
+        StereoCamera {
+            m_left: eye_camera(config, &stereo, -1.0),
+            m_right: eye_camera(config, &stereo, 1.0),
+            m_layout: stereo.layout,
+        }
+    }
+}
+
+// `side` is -1 for the left eye and 1 for the right one.
+fn eye_camera(config: &SceneConfig, stereo: &Stereo, side: f64) -> Arc<dyn Camera> {
+    let aspect_ratio = match stereo.layout {
+        StereoLayout::SideBySide => config.aspect_ratio / 2.0,
+        StereoLayout::OverUnder => config.aspect_ratio * 2.0,
+    };
+    let offset = side * stereo.interocular / 2.0;
+    if config.projection == Projection::Equirectangular {
+        let camera = EquirectangularCamera::new(config.lookfrom, config.lookat, config.vup);
+        return Arc::new(camera.with_eye(offset, stereo.convergence));
+    }
+
+    // parallel eyes, the thin lens ones converge by shifting their image
+    // window instead of turning, which would tilt the zero parallax plane
+    let (cu, _, cw) = basis(config.lookfrom, config.lookat, config.vup);
+    let mut eye = SceneConfig {
+        aspect_ratio,
+        lookfrom: config.lookfrom + offset * cu,
+        lookat: config.lookat + offset * cu,
+        stereo: None,
+        ..config.clone()
+    };
+    match config.projection {
+        Projection::Perspective if config.lens.is_none() => {
+            let window_width =
+                2.0 * stereo.convergence * (config.vfov.to_radians() / 2.0).tan() * aspect_ratio;
+            eye.tilt_shift.shift[0] -= offset / window_width;
+        }
+        // nothing to shift, turn to the point straight ahead at the
+        // convergence distance
+        _ => eye.lookat = config.lookfrom - stereo.convergence * cw,
+    }
+    build_mono_camera(&eye)
+}
+
+impl StereoCamera {
+    // The eye that sees `u`, `v`, and where that is on its own image.
+    fn eye(&self, u: f64, v: f64) -> (&dyn Camera, f64, f64) {
+        match self.m_layout {
+            StereoLayout::SideBySide if u < 0.5 => (self.m_left.as_ref(), 2.0 * u, v),
+            StereoLayout::SideBySide => (self.m_right.as_ref(), 2.0 * u - 1.0, v),
+            StereoLayout::OverUnder if v >= 0.5 => (self.m_left.as_ref(), u, 2.0 * v - 1.0),
+            StereoLayout::OverUnder => (self.m_right.as_ref(), u, 2.0 * v),
+        }
+    }
+}
+
+impl Camera for StereoCamera {
+    fn get_ray(&self, u: f64, v: f64) -> Option<Ray> {
+        let (eye, u, v) = self.eye(u, v);
+        eye.get_ray(u, v)
+    }
+
+    fn center_ray(&self, u: f64, v: f64) -> Option<Ray> {
+        let (eye, u, v) = self.eye(u, v);
+        eye.center_ray(u, v)
+    }
+}
@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+
+/// Tilt-shift lens movements of the perspective camera.
+///
+/// ```toml
+/// [config.tilt_shift]
+/// shift = [0.0, 0.2]
+/// tilt = [-4.0, 0.0]
+/// ```
+///
+/// Shifting instead of turning the camera up keeps vertical lines parallel
+/// in architectural shots. Tilting lays the plane of focus along e.g. a
+/// table top, it still passes through the point at `dist_to_focus` straight
+/// ahead.
+#[derive(Debug, Copy, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TiltShift {
+    /// Off-axis offset of the image as fractions of its width and height,
+    /// positive moves the view right and up.
+    pub shift: [f64; 2],
+    /// Degrees the plane of focus turns around the camera's horizontal and
+    /// vertical axes, positive moves its top and its right side away from the
+    /// camera.
+    pub tilt: [f64; 2],
+}
+
+impl TiltShift {
+    pub fn is_tilted(&self) -> bool {
+        self.tilt != [0.0, 0.0]
+    }
+}
@@ -1,12 +1,27 @@
 use image::Rgb;
 
-use crate::{camera, Vec3};
+use crate::{camera, ToneMapping, Vec3};
 
 pub type Color = Vec3;
 
+/// Rec. 709 relative luminance of a linear color.
+pub fn luminance(color: &Color) -> f64 {
+    0.2126 * color.x() + 0.7152 * color.y() + 0.0722 * color.z()
+}
+
+/// Decodes an sRGB encoded channel in `[0, 1]` to linear.
+pub fn srgb_to_linear(c: f64) -> f64 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
 pub trait ConvertToRGB {
     fn to_rgb(&self) -> Rgb<u8>;
     fn to_rgb_aa(&self, samples: u32) -> Rgb<u8>;
+    fn to_rgb_mapped(&self, tone_mapping: &ToneMapping) -> Rgb<u8>;
 }
 
 impl ConvertToRGB for Color {
@@ -34,4 +49,14 @@ impl ConvertToRGB for Color {
             (256.0 * camera::clamp(b, 0.0, 0.999)) as u8,
         ])
     }
+
+    fn to_rgb_mapped(&self, tone_mapping: &ToneMapping) -> Rgb<u8> {
+        let mapped = tone_mapping.apply(*self);
+
+        Rgb([
+            (256.0 * camera::clamp(mapped.x(), 0.0, 0.999)) as u8,
+            (256.0 * camera::clamp(mapped.y(), 0.0, 0.999)) as u8,
+            (256.0 * camera::clamp(mapped.z(), 0.0, 0.999)) as u8,
+        ])
+    }
 }
@@ -0,0 +1,272 @@
+use std::sync::{mpsc, Arc};
+
+use serde::{Deserialize, Serialize};
+use threadpool::ThreadPool;
+
+use crate::{luminance, AovBuffers, Color, FrameBuffer};
+
+/// Parameters of the feature guided joint bilateral denoiser.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DenoiseSettings {
+    /// Half width of the filter window in pixels.
+    pub radius: u32,
+    /// Falloff of the spatial weight, in pixels.
+    pub sigma_spatial: f64,
+    /// How many standard deviations of the estimated pixel noise two colors
+    /// may differ by and still be considered the same.
+    pub sigma_color: f64,
+    pub sigma_albedo: f64,
+    pub sigma_normal: f64,
+    /// Relative depth difference, as a fraction of the center pixel's depth.
+    pub sigma_depth: f64,
+    /// Also write the noisy image, to `<stem>_raw.<ext>`.
+    pub keep_raw: bool,
+}
+
+impl Default for DenoiseSettings {
+    fn default() -> Self {
+        DenoiseSettings {
+            radius: 7,
+            sigma_spatial: 4.0,
+            sigma_color: 2.0,
+            sigma_albedo: 0.1,
+            sigma_normal: 0.1,
+            sigma_depth: 0.05,
+            keep_raw: false,
+        }
+    }
+}
+
+/// Denoises `beauty` with a joint bilateral filter guided by the AOVs.
+///
+/// The radiance is divided by the albedo first so texture detail is not
+/// blurred, filtered, and multiplied back afterwards. Neighbours are weighted
+/// by screen distance, similarity of albedo, normal and depth, and by how far
+/// their luminance is from the center relative to the per pixel variance, so
+/// converged pixels are left mostly untouched. Where the variance is unknown,
+/// at one sample per pixel, only the guides weigh the neighbours.
+pub fn denoise(
+    beauty: &FrameBuffer,
+    aovs: &AovBuffers,
+    settings: &DenoiseSettings,
+    num_threads: usize,
+) -> FrameBuffer {
+    let width = beauty.width();
+    let height = beauty.height();
+    let beauty = Arc::new(beauty.clone());
+    let aovs = Arc::new(aovs.clone());
+    let settings = Arc::new(*settings);
+
+    let threadpool = ThreadPool::new(num_threads);
+    let (res_tx, res_rx) = mpsc::channel();
+    for y in 0..height {
+        let res_tx = res_tx.clone();
+        let beauty = Arc::clone(&beauty);
+        let aovs = Arc::clone(&aovs);
+        let settings = Arc::clone(&settings);
+        threadpool.execute(move || {
+            let row: Vec<Color> = (0..width)
+                .map(|x| denoise_pixel(&beauty, &aovs, &settings, x, y))
+                .collect();
+            res_tx.send((y, row)).expect("unable to send");
+        });
+    }
+    drop(res_tx);
+
+    let mut denoised = FrameBuffer::new(width, height);
+    for (y, row) in res_rx {
+        for (x, color) in row.into_iter().enumerate() {
+            denoised.put_pixel(x as u32, y, color);
+        }
+    }
+    denoised
+}
+
+fn denoise_pixel(
+    beauty: &FrameBuffer,
+    aovs: &AovBuffers,
+    settings: &DenoiseSettings,
+    x: u32,
+    y: u32,
+) -> Color {
+    const EPS: f64 = 1.0e-4;
+    let center = aovs.get_pixel(x, y);
+    let center_radiance = beauty.get_pixel(x, y);
+    let center_color = demodulate(center_radiance, center.albedo);
+
+    let radius = settings.radius as i64;
+    let x_range = (x as i64 - radius).max(0)..=(x as i64 + radius).min(beauty.width() as i64 - 1);
+    let y_range = (y as i64 - radius).max(0)..=(y as i64 + radius).min(beauty.height() as i64 - 1);
+
+    let mut sum = Color::empty();
+    let mut weight_sum = 0.0;
+    for qy in y_range {
+        for qx in x_range.clone() {
+            let (qx, qy) = (qx as u32, qy as u32);
+            let other = aovs.get_pixel(qx, qy);
+            let other_radiance = beauty.get_pixel(qx, qy);
+            let other_color = demodulate(other_radiance, other.albedo);
+
+            let dx = qx as f64 - x as f64;
+            let dy = qy as f64 - y as f64;
+            let spatial = (dx * dx + dy * dy) / (2.0 * settings.sigma_spatial.powi(2));
+
+            // the variance is of the mean luminance of the radiance, so compare
+            // luminance before demodulating
+            let noise = center.variance + other.variance;
+            let color = if noise.is_finite() {
+                let luminance_diff = luminance(&center_radiance) - luminance(&other_radiance);
+                luminance_diff.powi(2) / (settings.sigma_color.powi(2) * noise + EPS)
+            } else {
+                0.0
+            };
+
+            let albedo = (center.albedo - other.albedo).length_squared()
+                / (2.0 * settings.sigma_albedo.powi(2));
+
+            let geometry = if center.hit != other.hit {
+                f64::INFINITY
+            } else if center.hit {
+                let normal =
+                    (1.0 - center.normal.dot(&other.normal)).max(0.0) / settings.sigma_normal;
+                let depth = ((center.depth - other.depth)
+                    / (settings.sigma_depth * center.depth + EPS))
+                    .powi(2);
+                normal + depth
+            } else {
+                0.0
+            };
+
+            let weight = (-(spatial + color + albedo + geometry)).exp();
+            sum += weight * other_color;
+            weight_sum += weight;
+        }
+    }
+
+    let filtered = if weight_sum > 0.0 {
+        sum / weight_sum
+    } else {
+        center_color
+    };
+    remodulate(filtered, center.albedo)
+}
+
+fn demodulate(color: Color, albedo: Color) -> Color {
+    const MIN_ALBEDO: f64 = 1.0e-3;
+    Color::new(
+        color.x() / albedo.x().max(MIN_ALBEDO),
+        color.y() / albedo.y().max(MIN_ALBEDO),
+        color.z() / albedo.z().max(MIN_ALBEDO),
+    )
+}
+
+fn remodulate(color: Color, albedo: Color) -> Color {
+    const MIN_ALBEDO: f64 = 1.0e-3;
+    Color::new(
+        color.x() * albedo.x().max(MIN_ALBEDO),
+        color.y() * albedo.y().max(MIN_ALBEDO),
+        color.z() * albedo.z().max(MIN_ALBEDO),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AovSample, Point3, Vec3};
+
+    const SIZE: u32 = 16;
+
+    fn surface(normal: Vec3, variance: f64) -> AovSample {
+        AovSample {
+            albedo: Color::new(0.5, 0.5, 0.5),
+            normal,
+            depth: 1.0,
+            position: Point3::empty(),
+            object_id: Some(1),
+            hit: true,
+            variance,
+        }
+    }
+
+    fn buffers(sample: impl Fn(u32, u32) -> AovSample) -> AovBuffers {
+        let mut aovs = AovBuffers::new(SIZE, SIZE);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                aovs.put_pixel(x, y, &sample(x, y));
+            }
+        }
+        aovs
+    }
+
+    fn image(radiance: impl Fn(u32, u32) -> f64) -> FrameBuffer {
+        let mut framebuffer = FrameBuffer::new(SIZE, SIZE);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let value = radiance(x, y);
+                framebuffer.put_pixel(x, y, Color::new(value, value, value));
+            }
+        }
+        framebuffer
+    }
+
+    // deterministic noise in [-0.5, 0.5]
+    fn noise(x: u32, y: u32) -> f64 {
+        ((x * 7 + y * 13) % 5) as f64 / 4.0 - 0.5
+    }
+
+    fn mean_error(framebuffer: &FrameBuffer, expected: impl Fn(u32, u32) -> f64) -> f64 {
+        let mut error = 0.0;
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                error += (framebuffer.get_pixel(x, y).x() - expected(x, y)).abs();
+            }
+        }
+        error / (SIZE * SIZE) as f64
+    }
+
+    #[test]
+    fn keeps_constant_images() {
+        let beauty = image(|_, _| 0.75);
+        let aovs = buffers(|_, _| surface(Vec3::new(0.0, 0.0, 1.0), 0.0));
+        let denoised = denoise(&beauty, &aovs, &DenoiseSettings::default(), 2);
+        assert!(mean_error(&denoised, |_, _| 0.75) < 1e-6);
+    }
+
+    #[test]
+    fn smooths_single_sample_noise_without_crossing_normal_edges() {
+        // two flat surfaces meeting at x = 8, at one sample per pixel
+        let normal = |x: u32| {
+            if x < SIZE / 2 {
+                Vec3::new(0.0, 0.0, 1.0)
+            } else {
+                Vec3::new(1.0, 0.0, 0.0)
+            }
+        };
+        let level = |x: u32| if x < SIZE / 2 { 0.5 } else { 2.0 };
+        let beauty = image(|x, y| level(x) + noise(x, y));
+        let aovs = buffers(|x, _| surface(normal(x), f64::INFINITY));
+
+        let denoised = denoise(&beauty, &aovs, &DenoiseSettings::default(), 2);
+        let error = mean_error(&denoised, |x, _| level(x));
+        assert!(
+            error < 0.5 * mean_error(&beauty, |x, _| level(x)),
+            "{}",
+            error
+        );
+        for y in 0..SIZE {
+            assert!(denoised.get_pixel(SIZE / 2 - 1, y).x() < 1.0);
+            assert!(denoised.get_pixel(SIZE / 2, y).x() > 1.5);
+        }
+    }
+
+    #[test]
+    fn leaves_converged_pixels_alone() {
+        // a highlight that every sample agreed on
+        let beauty = image(|x, y| if (x, y) == (8, 8) { 4.0 } else { 0.5 });
+        let aovs = buffers(|_, _| surface(Vec3::new(0.0, 0.0, 1.0), 1.0e-6));
+        let denoised = denoise(&beauty, &aovs, &DenoiseSettings::default(), 2);
+        assert!((denoised.get_pixel(8, 8).x() - 4.0).abs() < 1e-3);
+        assert!((denoised.get_pixel(7, 8).x() - 0.5).abs() < 1e-3);
+    }
+}
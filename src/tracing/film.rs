@@ -0,0 +1,184 @@
+use std::ops::Range;
+
+use crate::{Color, Filter, FrameBuffer};
+
+/// Weighted sample accumulation for the whole image.
+///
+/// Workers splat into `FilmTile`s that cover their pixels plus the filter
+/// footprint, the tiles are then merged here and normalized into a `FrameBuffer`.
+pub struct Film {
+    m_width: u32,
+    m_height: u32,
+    m_pixels: Vec<(Color, f64)>,
+}
+
+impl Film {
+    pub fn new(width: u32, height: u32) -> Film {
+        Film {
+            m_width: width,
+            m_height: height,
+            m_pixels: vec![(Color::empty(), 0.0); (width * height) as usize],
+        }
+    }
+    pub fn merge(&mut self, tile: &FilmTile) {
+        for y in tile.m_y.clone() {
+            for x in tile.m_x.clone() {
+                let (color, weight) = tile.m_pixels[tile.index(x, y)];
+                let pixel = &mut self.m_pixels[(y * self.m_width + x) as usize];
+                pixel.0 += color;
+                pixel.1 += weight;
+            }
+        }
+    }
+    pub fn to_framebuffer(&self) -> FrameBuffer {
+        let mut framebuffer = FrameBuffer::new(self.m_width, self.m_height);
+        for y in 0..self.m_height {
+            for x in 0..self.m_width {
+                let (color, weight) = self.m_pixels[(y * self.m_width + x) as usize];
+                if weight != 0.0 {
+                    framebuffer.put_pixel(x, y, color / weight);
+                }
+            }
+        }
+        framebuffer
+    }
+}
+
+/// A rectangle of the film a single worker splats into.
+pub struct FilmTile {
+    m_x: Range<u32>,
+    m_y: Range<u32>,
+    m_pixels: Vec<(Color, f64)>,
+}
+
+impl FilmTile {
+    /// Creates the tile for rendering `pixels_x` x `pixels_y`, grown by the
+    /// filter radius so neighbouring pixels receive their share of the samples
+    /// and clipped to the image bounds.
+    pub fn new(
+        pixels_x: Range<u32>,
+        pixels_y: Range<u32>,
+        image_width: u32,
+        image_height: u32,
+        filter_radius: f64,
+    ) -> FilmTile {
+        let grow = filter_radius.ceil() as u32;
+        let x = pixels_x.start.saturating_sub(grow)..(pixels_x.end + grow).min(image_width);
+        let y = pixels_y.start.saturating_sub(grow)..(pixels_y.end + grow).min(image_height);
+        let size = (x.end - x.start) * (y.end - y.start);
+        FilmTile {
+            m_x: x,
+            m_y: y,
+            m_pixels: vec![(Color::empty(), 0.0); size as usize],
+        }
+    }
+
+    /// Splats a sample taken at continuous image coordinates `(x, y)`, where
+    /// pixel `(i, j)` spans `[i, i + 1) x [j, j + 1)`.
+    pub fn add_sample(&mut self, x: f64, y: f64, color: Color, filter: &impl Filter) {
+        let radius = filter.radius();
+        let x_min = ((x - 0.5 - radius).ceil().max(self.m_x.start as f64)) as u32;
+        let x_max = ((x - 0.5 + radius).floor() + 1.0).min(self.m_x.end as f64);
+        let y_min = ((y - 0.5 - radius).ceil().max(self.m_y.start as f64)) as u32;
+        let y_max = ((y - 0.5 + radius).floor() + 1.0).min(self.m_y.end as f64);
+        if x_max <= 0.0 || y_max <= 0.0 {
+            return;
+        }
+
+        for py in y_min..y_max as u32 {
+            for px in x_min..x_max as u32 {
+                let weight = filter.evaluate(px as f64 + 0.5 - x, py as f64 + 0.5 - y);
+                if weight != 0.0 {
+                    let ix = self.index(px, py);
+                    self.m_pixels[ix].0 += weight * color;
+                    self.m_pixels[ix].1 += weight;
+                }
+            }
+        }
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        ((y - self.m_y.start) * (self.m_x.end - self.m_x.start) + (x - self.m_x.start)) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PixelFilter;
+
+    const FILTERS: [PixelFilter; 4] = [
+        PixelFilter::Box { radius: 0.5 },
+        PixelFilter::Tent { radius: 1.0 },
+        PixelFilter::Mitchell {
+            radius: 2.0,
+            b: 1.0 / 3.0,
+            c: 1.0 / 3.0,
+        },
+        PixelFilter::Lanczos {
+            radius: 3.0,
+            tau: 3.0,
+        },
+    ];
+
+    fn assert_close(a: Color, b: Color) {
+        assert!((a - b).length() < 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn constant_radiance_is_normalized_back_unchanged() {
+        let constant = Color::new(0.25, 0.5, 2.0);
+        for filter in FILTERS {
+            let mut film = Film::new(6, 4);
+            // two tiles, so samples near the seam land in both
+            for pixels_x in [0..3, 3..6] {
+                let mut tile = FilmTile::new(pixels_x.clone(), 0..4, 6, 4, filter.radius());
+                for y in 0..4 {
+                    for x in pixels_x.clone() {
+                        for (dx, dy) in [(0.2, 0.3), (0.7, 0.6), (0.5, 0.5)] {
+                            tile.add_sample(x as f64 + dx, y as f64 + dy, constant, &filter);
+                        }
+                    }
+                }
+                film.merge(&tile);
+            }
+
+            let framebuffer = film.to_framebuffer();
+            for y in 0..4 {
+                for x in 0..6 {
+                    assert_close(framebuffer.get_pixel(x, y), constant);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn box_filter_averages_the_samples_of_a_pixel() {
+        let filter = PixelFilter::default();
+        let mut film = Film::new(2, 1);
+        let mut tile = FilmTile::new(0..2, 0..1, 2, 1, filter.radius());
+        tile.add_sample(0.25, 0.5, Color::new(1.0, 0.0, 0.0), &filter);
+        tile.add_sample(0.75, 0.5, Color::new(0.0, 1.0, 0.0), &filter);
+        film.merge(&tile);
+
+        let framebuffer = film.to_framebuffer();
+        assert_close(framebuffer.get_pixel(0, 0), Color::new(0.5, 0.5, 0.0));
+        // no samples, no weight
+        assert_close(framebuffer.get_pixel(1, 0), Color::empty());
+    }
+
+    #[test]
+    fn wide_filters_spread_into_neighbouring_pixels() {
+        let filter = PixelFilter::Tent { radius: 1.0 };
+        let mut film = Film::new(3, 1);
+        let mut tile = FilmTile::new(1..2, 0..1, 3, 1, filter.radius());
+        // a third of the way from the center of pixel 1 to pixel 2
+        tile.add_sample(1.5 + 1.0 / 3.0, 0.5, Color::new(1.0, 1.0, 1.0), &filter);
+        film.merge(&tile);
+
+        let framebuffer = film.to_framebuffer();
+        assert_close(framebuffer.get_pixel(0, 0), Color::empty());
+        assert_close(framebuffer.get_pixel(1, 0), Color::new(1.0, 1.0, 1.0));
+        assert_close(framebuffer.get_pixel(2, 0), Color::new(1.0, 1.0, 1.0));
+    }
+}
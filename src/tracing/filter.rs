@@ -0,0 +1,180 @@
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+/// Pixel reconstruction filter, evaluated at an offset from the pixel center
+/// measured in pixels.
+pub trait Filter: Send + Sync {
+    fn radius(&self) -> f64;
+    fn evaluate(&self, x: f64, y: f64) -> f64;
+}
+
+/// The reconstruction filters selectable from `SceneConfig`.
+///
+/// All of them are separable, so `evaluate(x, y)` is the product of the 1D kernel
+/// in both directions.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PixelFilter {
+    Box {
+        radius: f64,
+    },
+    Tent {
+        radius: f64,
+    },
+    Gaussian {
+        radius: f64,
+        alpha: f64,
+    },
+    /// Mitchell-Netravali, `b` and `c` of 1/3 is the usual recommendation.
+    Mitchell {
+        radius: f64,
+        b: f64,
+        c: f64,
+    },
+    /// Lanczos windowed sinc, `tau` is the number of sinc cycles in the window.
+    Lanczos {
+        radius: f64,
+        tau: f64,
+    },
+}
+
+impl PixelFilter {
+    fn evaluate_1d(&self, x: f64) -> f64 {
+        let x = x.abs();
+        match *self {
+            PixelFilter::Box { radius } => {
+                if x <= radius {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            PixelFilter::Tent { radius } => (radius - x).max(0.0),
+            PixelFilter::Gaussian { radius, alpha } => {
+                ((-alpha * x * x).exp() - (-alpha * radius * radius).exp()).max(0.0)
+            }
+            PixelFilter::Mitchell { radius, b, c } => mitchell_1d(2.0 * x / radius, b, c),
+            PixelFilter::Lanczos { radius, tau } => {
+                if x > radius {
+                    0.0
+                } else {
+                    sinc(x) * sinc(x / tau)
+                }
+            }
+        }
+    }
+}
+
+impl Default for PixelFilter {
+    fn default() -> Self {
+        // matches plain per pixel averaging
+        PixelFilter::Box { radius: 0.5 }
+    }
+}
+
+impl Filter for PixelFilter {
+    fn radius(&self) -> f64 {
+        match *self {
+            PixelFilter::Box { radius }
+            | PixelFilter::Tent { radius }
+            | PixelFilter::Gaussian { radius, .. }
+            | PixelFilter::Mitchell { radius, .. }
+            | PixelFilter::Lanczos { radius, .. } => radius,
+        }
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        self.evaluate_1d(x) * self.evaluate_1d(y)
+    }
+}
+
+fn mitchell_1d(x: f64, b: f64, c: f64) -> f64 {
+    let x = x.abs();
+    if x > 2.0 {
+        0.0
+    } else if x > 1.0 {
+        ((-b - 6.0 * c) * x.powi(3)
+            + (6.0 * b + 30.0 * c) * x.powi(2)
+            + (-12.0 * b - 48.0 * c) * x
+            + (8.0 * b + 24.0 * c))
+            / 6.0
+    } else {
+        ((12.0 - 9.0 * b - 6.0 * c) * x.powi(3)
+            + (-18.0 + 12.0 * b + 6.0 * c) * x.powi(2)
+            + (6.0 - 2.0 * b))
+            / 6.0
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1.0e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILTERS: [PixelFilter; 5] = [
+        PixelFilter::Box { radius: 0.5 },
+        PixelFilter::Tent { radius: 1.0 },
+        PixelFilter::Gaussian {
+            radius: 1.5,
+            alpha: 2.0,
+        },
+        PixelFilter::Mitchell {
+            radius: 2.0,
+            b: 1.0 / 3.0,
+            c: 1.0 / 3.0,
+        },
+        PixelFilter::Lanczos {
+            radius: 3.0,
+            tau: 3.0,
+        },
+    ];
+
+    #[test]
+    fn filters_vanish_outside_their_radius() {
+        for filter in FILTERS {
+            let outside = filter.radius() + 0.01;
+            assert_eq!(filter.evaluate(outside, 0.0), 0.0, "{:?}", filter);
+            assert_eq!(filter.evaluate(0.0, -outside), 0.0, "{:?}", filter);
+            assert!(filter.evaluate(0.0, 0.0) > 0.0, "{:?}", filter);
+        }
+    }
+
+    #[test]
+    fn filters_are_symmetric_and_separable() {
+        for filter in FILTERS {
+            let (x, y) = (0.3, 0.2);
+            let value = filter.evaluate(x, y);
+            assert_eq!(filter.evaluate(-x, y), value, "{:?}", filter);
+            assert_eq!(filter.evaluate(x, -y), value, "{:?}", filter);
+            let separable =
+                filter.evaluate(x, 0.0) * filter.evaluate(0.0, y) / filter.evaluate(0.0, 0.0);
+            assert!((separable - value).abs() < 1e-12, "{:?}", filter);
+        }
+    }
+
+    #[test]
+    fn mitchell_weights_sum_to_one_across_pixels() {
+        let filter = FILTERS[3];
+        for offset in [0.0, 0.1, 0.25, 0.5] {
+            let sum: f64 = (-2..=2)
+                .map(|i| filter.evaluate_1d(i as f64 + offset))
+                .sum();
+            assert!((sum - 1.0).abs() < 1e-12, "{} at {}", sum, offset);
+        }
+    }
+
+    #[test]
+    fn default_box_covers_a_single_pixel() {
+        let filter = PixelFilter::default();
+        assert_eq!(filter.evaluate(0.49, -0.49), 1.0);
+        assert_eq!(filter.evaluate(0.51, 0.0), 0.0);
+    }
+}
@@ -0,0 +1,271 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use image::{
+    codecs::hdr::HdrEncoder,
+    error::{ParameterError, ParameterErrorKind},
+    ImageError, ImageResult, Rgb, Rgb32FImage, RgbImage,
+};
+
+use crate::{Color, ConvertToRGB, RenderRegion, ToneMapping};
+
+/// Linear, floating point render target.
+///
+/// Pixels hold the final (sample averaged) radiance for the pixel without any
+/// gamma or clamping applied, so the full dynamic range survives until the
+/// image is written.
+#[derive(Debug, Clone)]
+pub struct FrameBuffer {
+    m_image: Rgb32FImage,
+}
+
+impl FrameBuffer {
+    pub fn new(width: u32, height: u32) -> FrameBuffer {
+        FrameBuffer {
+            m_image: Rgb32FImage::new(width, height),
+        }
+    }
+    pub fn width(&self) -> u32 {
+        self.m_image.width()
+    }
+    pub fn height(&self) -> u32 {
+        self.m_image.height()
+    }
+    pub fn put_pixel(&mut self, x: u32, y: u32, color: Color) {
+        self.m_image.put_pixel(
+            x,
+            y,
+            Rgb([color.x() as f32, color.y() as f32, color.z() as f32]),
+        );
+    }
+    pub fn get_pixel(&self, x: u32, y: u32) -> Color {
+        let Rgb([r, g, b]) = *self.m_image.get_pixel(x, y);
+        Color::new(r as f64, g as f64, b as f64)
+    }
+    pub fn as_rgb32f(&self) -> &Rgb32FImage {
+        &self.m_image
+    }
+    pub fn to_rgb(&self, tone_mapping: &ToneMapping) -> RgbImage {
+        RgbImage::from_fn(self.width(), self.height(), |x, y| {
+            self.get_pixel(x, y).to_rgb_mapped(tone_mapping)
+        })
+    }
+
+    /// Just the pixels in `region`.
+    pub fn crop(&self, region: &RenderRegion) -> FrameBuffer {
+        let mut cropped = FrameBuffer::new(region.width, region.height);
+        for y in region.rows() {
+            for x in region.columns() {
+                cropped.put_pixel(x - region.x, y - region.y, self.get_pixel(x, y));
+            }
+        }
+        cropped
+    }
+    /// Blacks out every pixel outside `region`.
+    pub fn clear_outside(&mut self, region: &RenderRegion) {
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                if !region.contains(x, y) {
+                    self.put_pixel(x, y, Color::empty());
+                }
+            }
+        }
+    }
+
+    /// Writes the buffer to `path`, picking the encoding from the extension.
+    ///
+    /// `.exr`, `.hdr` and `.pfm` keep the linear floating point values, every
+    /// other extension is tone mapped, quantized to 8 bits and handed to `image`.
+    pub fn save<P: AsRef<Path>>(&self, path: P, tone_mapping: &ToneMapping) -> ImageResult<()> {
+        let path = path.as_ref();
+        match output_extension(path).as_deref() {
+            Some("exr") => self.m_image.save(path),
+            Some("hdr") => self.save_hdr(path),
+            Some("pfm") => self.save_pfm(path),
+            _ => self.to_rgb(tone_mapping).save(path),
+        }
+    }
+
+    /// Like `save`, but only the pixels in `region` are written over the
+    /// image at `background`, which needs the same size as the buffer.
+    ///
+    /// Floating point outputs take the linear values of the background, other
+    /// outputs its 8 bit ones, so it should be an earlier render in the same
+    /// format.
+    pub fn save_over<P: AsRef<Path>>(
+        &self,
+        path: P,
+        tone_mapping: &ToneMapping,
+        background: &Path,
+        region: &RenderRegion,
+    ) -> ImageResult<()> {
+        let path = path.as_ref();
+        let background = image::open(background)?;
+        if (background.width(), background.height()) != (self.width(), self.height()) {
+            return Err(ImageError::Parameter(ParameterError::from_kind(
+                ParameterErrorKind::DimensionMismatch,
+            )));
+        }
+        match output_extension(path).as_deref() {
+            Some("exr" | "hdr" | "pfm") => {
+                let mut composite = FrameBuffer {
+                    m_image: background.to_rgb32f(),
+                };
+                for y in region.rows() {
+                    for x in region.columns() {
+                        composite.put_pixel(x, y, self.get_pixel(x, y));
+                    }
+                }
+                composite.save(path, tone_mapping)
+            }
+            _ => {
+                let mut composite = background.to_rgb8();
+                for y in region.rows() {
+                    for x in region.columns() {
+                        composite.put_pixel(x, y, self.get_pixel(x, y).to_rgb_mapped(tone_mapping));
+                    }
+                }
+                composite.save(path)
+            }
+        }
+    }
+
+    fn save_hdr(&self, path: &Path) -> ImageResult<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        let pixels: Vec<Rgb<f32>> = self.m_image.pixels().copied().collect();
+        HdrEncoder::new(writer).encode(&pixels, self.width() as usize, self.height() as usize)
+    }
+
+    fn save_pfm(&self, path: &Path) -> ImageResult<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        // negative scale marks the data as little endian
+        write!(writer, "PF\n{} {}\n-1.0\n", self.width(), self.height())?;
+        // PFM scanlines run bottom to top
+        for y in (0..self.height()).rev() {
+            for x in 0..self.width() {
+                for channel in self.m_image.get_pixel(x, y).0 {
+                    writer.write_all(&channel.to_le_bytes())?;
+                }
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+fn output_extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    // a 2x3 buffer whose pixels all differ, brighter than 1.0 to the bottom
+    fn gradient() -> FrameBuffer {
+        let mut framebuffer = FrameBuffer::new(2, 3);
+        for y in 0..3 {
+            for x in 0..2 {
+                framebuffer.put_pixel(x, y, Color::new(x as f64, y as f64, 0.25 + y as f64));
+            }
+        }
+        framebuffer
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("framebuffer_{}_{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn writes_pfm_bottom_up_and_little_endian() {
+        let path = temp_path("orientation.pfm");
+        gradient().save(&path, &ToneMapping::default()).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let header = b"PF\n2 3\n-1.0\n";
+        assert!(bytes.starts_with(header));
+        let values: Vec<f32> = bytes[header.len()..]
+            .chunks_exact(4)
+            .map(|value| f32::from_le_bytes(value.try_into().unwrap()))
+            .collect();
+        assert_eq!(values.len(), 2 * 3 * 3);
+        // the first scanline is the bottom row, y = 2
+        assert_eq!(values[..6], [0.0, 2.0, 2.25, 1.0, 2.0, 2.25]);
+        assert_eq!(values[12..], [0.0, 0.0, 0.25, 1.0, 0.0, 0.25]);
+    }
+
+    #[test]
+    fn round_trips_exr() {
+        let path = temp_path("round_trip.exr");
+        gradient().save(&path, &ToneMapping::default()).unwrap();
+        let image = image::open(&path).unwrap().to_rgb32f();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(image.dimensions(), (2, 3));
+        assert_eq!(*image.get_pixel(1, 2), Rgb([1.0, 2.0, 2.25]));
+    }
+
+    #[test]
+    fn round_trips_hdr_top_down() {
+        let path = temp_path("round_trip.hdr");
+        gradient().save(&path, &ToneMapping::default()).unwrap();
+        let reader = std::io::BufReader::new(File::open(&path).unwrap());
+        let pixels = image::codecs::hdr::HdrDecoder::new(reader)
+            .unwrap()
+            .read_image_hdr()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        // rgbe shares one exponent between the channels
+        let Rgb([r, g, b]) = pixels[2 * 2 + 1];
+        assert!((r - 1.0).abs() < 0.02);
+        assert!((g - 2.0).abs() < 0.02);
+        assert!((b - 2.25).abs() < 0.02);
+    }
+
+    #[test]
+    fn tone_maps_other_formats() {
+        let rgb = gradient().to_rgb(&ToneMapping::default());
+        assert_eq!(*rgb.get_pixel(0, 0), Rgb([0, 0, 137]));
+        assert_eq!(*rgb.get_pixel(1, 2), Rgb([255, 255, 255]));
+    }
+
+    #[test]
+    fn crops_to_the_region() {
+        let region = RenderRegion {
+            x: 1,
+            y: 1,
+            width: 1,
+            height: 2,
+            crop: true,
+            background: None,
+        };
+        let cropped = gradient().crop(&region);
+        assert_eq!((cropped.width(), cropped.height()), (1, 2));
+        assert_eq!(cropped.get_pixel(0, 0), Color::new(1.0, 1.0, 1.25));
+        assert_eq!(cropped.get_pixel(0, 1), Color::new(1.0, 2.0, 2.25));
+    }
+
+    #[test]
+    fn clears_everything_outside_the_region() {
+        let region = RenderRegion {
+            x: 0,
+            y: 1,
+            width: 2,
+            height: 1,
+            crop: false,
+            background: None,
+        };
+        let mut framebuffer = gradient();
+        framebuffer.clear_outside(&region);
+        assert_eq!(framebuffer.get_pixel(1, 0), Color::empty());
+        assert_eq!(framebuffer.get_pixel(1, 1), Color::new(1.0, 1.0, 1.25));
+        assert_eq!(framebuffer.get_pixel(0, 2), Color::empty());
+    }
+}
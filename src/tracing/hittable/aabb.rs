@@ -0,0 +1,57 @@
+use crate::{Point3, Ray};
+
+/// Axis aligned bounding box.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: Point3,
+    pub max: Point3,
+}
+
+impl Aabb {
+    pub fn new(min: Point3, max: Point3) -> Aabb {
+        Aabb { min, max }
+    }
+    pub fn empty() -> Aabb {
+        Aabb {
+            min: Point3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: Point3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        }
+    }
+    pub fn grow(&self, p: Point3) -> Aabb {
+        Aabb::new(self.min.min(&p), self.max.max(&p))
+    }
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb::new(self.min.min(&other.min), self.max.max(&other.max))
+    }
+    pub fn center(&self) -> Point3 {
+        0.5 * (self.min + self.max)
+    }
+    pub fn largest_axis(&self) -> usize {
+        let extent = self.max - self.min;
+        if extent.x() >= extent.y() && extent.x() >= extent.z() {
+            0
+        } else if extent.y() >= extent.z() {
+            1
+        } else {
+            2
+        }
+    }
+    /// Slab test, whether the ray enters the box within `t_bounds`.
+    pub fn hit(&self, ray: &Ray, t_bounds: (f64, f64)) -> bool {
+        let (mut t_min, mut t_max) = t_bounds;
+        for axis in 0..3 {
+            let inv_d = 1.0 / ray.direction().axis(axis);
+            let mut t0 = (self.min.axis(axis) - ray.origin().axis(axis)) * inv_d;
+            let mut t1 = (self.max.axis(axis) - ray.origin().axis(axis)) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max < t_min {
+                return false;
+            }
+        }
+        true
+    }
+}
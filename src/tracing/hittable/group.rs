@@ -0,0 +1,46 @@
+use crate::{Point3, Transform, Vec3};
+
+/// Node of the scene graph. Objects and groups whose path starts with the
+/// group's path followed by `/` are placed relative to it, so moving a group
+/// moves everything in it.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Group {
+    /// Relative to the parent group.
+    pub position: Point3,
+    /// Rotation around x, then y, then z, in degrees.
+    pub rotation: Vec3,
+    pub scale: Vec3,
+}
+
+impl Group {
+    pub fn new(position: Point3) -> Group {
+        Group {
+            position,
+            ..Group::default()
+        }
+    }
+
+    /// Maps the group's space to its parent's.
+    pub fn transform(&self) -> Transform {
+        Transform::translate(self.position)
+            * Transform::rotate(self.rotation.z(), Vec3::new(0.0, 0.0, 1.0))
+            * Transform::rotate(self.rotation.y(), Vec3::new(0.0, 1.0, 0.0))
+            * Transform::rotate(self.rotation.x(), Vec3::new(1.0, 0.0, 0.0))
+            * Transform::scale(self.scale.x(), self.scale.y(), self.scale.z())
+    }
+}
+
+impl Default for Group {
+    fn default() -> Self {
+        Group {
+            position: Point3::empty(),
+            rotation: Vec3::empty(),
+            scale: Vec3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+/// Parent group of a path, `car` for `car/wheel_fl`.
+pub fn parent_path(path: &str) -> Option<&str> {
+    path.rsplit_once('/').map(|(parent, _)| parent)
+}
@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use crate::{
     tracing::{Point3, Ray, Vec3},
-    Scatter,
+    Color, Scatter,
 };
 
 pub struct HitRecord {
@@ -11,6 +11,13 @@ pub struct HitRecord {
     pub t: f64,
     pub front_face: bool,
     pub mat: Arc<dyn Scatter>,
+    /// Id of the named `HittableMap` object that was hit, if any.
+    pub object_id: Option<u32>,
+    /// Texture coordinates, zero for surfaces without any.
+    pub u: f64,
+    pub v: f64,
+    /// Interpolated vertex color, white for surfaces without any.
+    pub vertex_color: Color,
 }
 
 impl HitRecord {
@@ -28,6 +35,10 @@ impl HitRecord {
                 normal: -outward_normal,
                 front_face: false,
                 mat,
+                object_id: None,
+                u: 0.0,
+                v: 0.0,
+                vertex_color: Color::new(1.0, 1.0, 1.0),
             }
         } else {
             HitRecord {
@@ -36,6 +47,10 @@ impl HitRecord {
                 normal: outward_normal,
                 front_face: true,
                 mat,
+                object_id: None,
+                u: 0.0,
+                v: 0.0,
+                vertex_color: Color::new(1.0, 1.0, 1.0),
             }
         }
     }
@@ -22,6 +22,12 @@ impl HittableList {
     }
 }
 
+impl Default for HittableList {
+    fn default() -> Self {
+        HittableList::new()
+    }
+}
+
 impl Hittable for HittableList {
     fn hit(&self, ray: &Ray, t_bounds: (f64, f64)) -> Option<HitRecord> {
         let mut ret_val = None;
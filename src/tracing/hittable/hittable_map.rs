@@ -1,15 +1,70 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-use crate::{tracing::ray::Ray, Anchored, HitRecord, Hittable};
+use serde::{Deserialize, Serialize};
 
+use crate::{
+    parent_path, tracing::ray::Ray, Anchored, Background, Color, Group, HitRecord, Hittable,
+    Point3, RayKind, Transform,
+};
+
+/// Which rays see an object, everything is visible by default.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Visibility {
+    /// Seen directly by the camera.
+    pub camera: bool,
+    /// Blocks diffusely scattered light, which is what casts its shadow.
+    pub shadows: bool,
+    /// Seen in mirror reflections and through refractions.
+    pub reflections: bool,
+}
+
+impl Visibility {
+    pub fn visible_to(&self, kind: RayKind) -> bool {
+        match kind {
+            RayKind::Camera => self.camera,
+            RayKind::Diffuse => self.shadows,
+            RayKind::Specular => self.reflections,
+        }
+    }
+}
+
+impl Default for Visibility {
+    fn default() -> Self {
+        Visibility {
+            camera: true,
+            shadows: true,
+            reflections: true,
+        }
+    }
+}
+
+struct Entry {
+    object: Box<dyn Anchored>,
+    visibility: Visibility,
+    // world transform from the ancestor groups, `None` when it's the identity
+    transform: Option<Transform>,
+}
+
+/// Named objects, optionally organised in groups by path (`car/wheel_fl`).
+///
+/// Objects are positioned relative to their parent group. The world
+/// transform of every object is resolved whenever a group changes, so
+/// rendering only has to look it up. Objects are kept in name order, which
+/// is also the order they're intersected in, so ties are broken the same way
+/// on every run.
 pub struct HittableMap {
-    pub objects: HashMap<String, Box<dyn Anchored>>,
+    objects: BTreeMap<String, Entry>,
+    pub background: Background,
+    groups: HashMap<String, Group>,
 }
 
 impl HittableMap {
     pub fn new() -> HittableMap {
         HittableMap {
-            objects: HashMap::new(),
+            objects: BTreeMap::new(),
+            background: Background::default(),
+            groups: HashMap::new(),
         }
     }
     pub fn from(name: String, object: Box<dyn Anchored>) -> HittableMap {
@@ -17,9 +72,204 @@ impl HittableMap {
         h.add(name, object);
         h
     }
+    /// Adds an object, or replaces the one with the same name, visible to
+    /// every ray. Missing parent groups are created untransformed.
     pub fn add(&mut self, name: String, object: Box<dyn Anchored>) {
-        self.objects.insert(name, object);
+        self.add_parents(&name);
+        let entry = Entry {
+            object,
+            visibility: Visibility::default(),
+            transform: self.parent_transform(&name),
+        };
+        self.objects.insert(name, entry);
+    }
+    pub fn remove(&mut self, name: &str) -> Option<Box<dyn Anchored>> {
+        self.objects.remove(name).map(|entry| entry.object)
+    }
+    /// Moves an object to a new name, which may put it in another group.
+    /// Returns `false` without changing anything if there's no object named
+    /// `from` or `to` is taken.
+    pub fn rename(&mut self, from: &str, to: &str) -> bool {
+        if !self.objects.contains_key(from) || self.objects.contains_key(to) {
+            return false;
+        }
+        let mut entry = self.objects.remove(from).expect("checked above");
+        self.add_parents(to);
+        entry.transform = self.parent_transform(to);
+        self.objects.insert(to.to_string(), entry);
+        true
+    }
+    pub fn get(&self, name: &str) -> Option<&dyn Anchored> {
+        self.objects.get(name).map(|entry| entry.object.as_ref())
+    }
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut dyn Anchored> {
+        self.objects
+            .get_mut(name)
+            .map(|entry| entry.object.as_mut() as &mut dyn Anchored)
+    }
+    pub fn contains(&self, name: &str) -> bool {
+        self.objects.contains_key(name)
+    }
+    /// Objects in name order.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &dyn Anchored)> {
+        self.objects
+            .iter()
+            .map(|(name, entry)| (name, entry.object.as_ref()))
+    }
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.objects.keys()
+    }
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+    pub fn visibility(&self, name: &str) -> Option<Visibility> {
+        self.objects.get(name).map(|entry| entry.visibility)
+    }
+    /// Returns `false` if there's no object named `name`.
+    pub fn set_visibility(&mut self, name: &str, visibility: Visibility) -> bool {
+        match self.objects.get_mut(name) {
+            Some(entry) => {
+                entry.visibility = visibility;
+                true
+            }
+            None => false,
+        }
+    }
+    /// Adds or replaces a group, missing parent groups are created
+    /// untransformed.
+    pub fn add_group(&mut self, path: String, group: Group) {
+        self.add_parents(&path);
+        self.groups.insert(path, group);
+        self.resolve();
+    }
+    pub fn group(&self, path: &str) -> Option<&Group> {
+        self.groups.get(path)
     }
+    /// Replaces the transform of an existing group, `false` if there's none.
+    pub fn set_group(&mut self, path: &str, group: Group) -> bool {
+        match self.groups.get_mut(path) {
+            Some(existing) => {
+                *existing = group;
+                self.resolve();
+                true
+            }
+            None => false,
+        }
+    }
+    pub fn groups(&self) -> impl Iterator<Item = (&String, &Group)> {
+        self.groups.iter()
+    }
+    /// Position of a group or object relative to its parent group.
+    pub fn origin(&self, path: &str) -> Option<Point3> {
+        match self.groups.get(path) {
+            Some(group) => Some(group.position),
+            None => self.get(path).map(|object| object.origin()),
+        }
+    }
+    /// Moves a group or object relative to its parent group, `false` if the
+    /// path names neither.
+    pub fn set_origin(&mut self, path: &str, origin: Point3) -> bool {
+        if let Some(group) = self.groups.get(path) {
+            let group = Group {
+                position: origin,
+                ..*group
+            };
+            self.set_group(path, group)
+        } else if let Some(object) = self.get_mut(path) {
+            object.set_origin(origin);
+            true
+        } else {
+            false
+        }
+    }
+    /// Position of a group or object in world space.
+    pub fn world_origin(&self, path: &str) -> Option<Point3> {
+        let origin = self.origin(path)?;
+        Some(match parent_path(path) {
+            Some(parent) => self.world_transform(parent).point(origin),
+            None => origin,
+        })
+    }
+    /// Maps the space of a group, or the parent space of an object, to world
+    /// space.
+    pub fn world_transform(&self, path: &str) -> Transform {
+        let own = self.groups.get(path).map(Group::transform);
+        let parent = parent_path(path).map(|parent| self.world_transform(parent));
+        match (parent, own) {
+            (Some(parent), Some(own)) => parent * own,
+            (Some(parent), None) => parent,
+            (None, Some(own)) => own,
+            (None, None) => Transform::identity(),
+        }
+    }
+    /// Stable id for an object name, written to the object id AOV.
+    ///
+    /// This is a 32 bit FNV-1a hash of the name so ids stay the same between
+    /// renders and processes.
+    pub fn object_id(name: &str) -> u32 {
+        name.bytes().fold(0x811c9dc5, |hash, byte| {
+            (hash ^ byte as u32).wrapping_mul(0x01000193)
+        })
+    }
+    pub fn name_of(&self, object_id: u32) -> Option<&str> {
+        self.names()
+            .find(|name| HittableMap::object_id(name) == object_id)
+            .map(|name| name.as_str())
+    }
+
+    fn add_parents(&mut self, path: &str) {
+        let mut parent = parent_path(path);
+        while let Some(path) = parent {
+            self.groups.entry(path.to_string()).or_default();
+            parent = parent_path(path);
+        }
+    }
+
+    fn parent_transform(&self, name: &str) -> Option<Transform> {
+        let transform = self.world_transform(parent_path(name)?);
+        (!transform.is_identity()).then_some(transform)
+    }
+
+    fn resolve(&mut self) {
+        let transforms: Vec<Option<Transform>> = self
+            .objects
+            .keys()
+            .map(|name| self.parent_transform(name))
+            .collect();
+        for (entry, transform) in self.objects.values_mut().zip(transforms) {
+            entry.transform = transform;
+        }
+    }
+}
+
+impl Default for HittableMap {
+    fn default() -> Self {
+        HittableMap::new()
+    }
+}
+
+// Intersects an object placed by `transform` by moving the ray into the
+// object's space. The ray parameter is unchanged since the direction is
+// transformed without normalizing.
+fn hit_transformed(
+    object: &dyn Anchored,
+    transform: &Transform,
+    ray: &Ray,
+    t_bounds: (f64, f64),
+) -> Option<HitRecord> {
+    let inverse = transform.inverse();
+    let local = Ray::new(
+        inverse.point(*ray.origin()),
+        inverse.vector(*ray.direction()),
+    )
+    .with_kind(ray.kind());
+    let mut rec = object.hit(&local, t_bounds)?;
+    rec.p = transform.point(rec.p);
+    rec.normal = transform.normal(rec.normal).unit_vector();
+    Some(rec)
 }
 
 impl Hittable for HittableMap {
@@ -27,12 +277,25 @@ impl Hittable for HittableMap {
         let mut ret_val = None;
         let mut closest_so_far = t_bounds.1;
 
-        for (_name, object) in &self.objects {
-            if let Some(rec) = object.hit(ray, (t_bounds.0, closest_so_far)) {
+        for (name, entry) in &self.objects {
+            if !entry.visibility.visible_to(ray.kind()) {
+                continue;
+            }
+            let t_bounds = (t_bounds.0, closest_so_far);
+            let hit = match &entry.transform {
+                Some(transform) => hit_transformed(entry.object.as_ref(), transform, ray, t_bounds),
+                None => entry.object.hit(ray, t_bounds),
+            };
+            if let Some(mut rec) = hit {
                 closest_so_far = rec.t;
+                rec.object_id = Some(HittableMap::object_id(name));
                 ret_val = Some(rec);
             }
         }
         ret_val
     }
+
+    fn background(&self, ray: &Ray) -> Color {
+        self.background.color(ray)
+    }
 }
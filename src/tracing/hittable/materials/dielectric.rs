@@ -1,6 +1,6 @@
 use rand::Rng;
 
-use crate::{Color, Ray, Scatter};
+use crate::{Color, MaterialDescription, Ray, RayKind, Scatter};
 
 pub struct Dielectric {
     ir: f64,
@@ -35,7 +35,7 @@ impl Scatter for Dielectric {
         let cos_theta = (-unit_direction).dot(&hit_record.normal).min(1.0);
         let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
 
-        let mut rng = rand::thread_rng();
+        let mut rng = crate::rng();
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
         let will_refract = rng.gen::<f64>() < Self::reflectance(cos_theta, refraction_ratio);
 
@@ -44,8 +44,12 @@ impl Scatter for Dielectric {
         } else {
             unit_direction.refract(&hit_record.normal, refraction_ratio)
         };
-        let scattered = Ray::new(hit_record.p, direction);
+        let scattered = Ray::new(hit_record.p, direction).with_kind(RayKind::Specular);
 
         Some((Color::new(1.0, 1.0, 1.0), scattered))
     }
+
+    fn describe(&self) -> Option<MaterialDescription> {
+        Some(MaterialDescription::Dielectric { ior: self.ir })
+    }
 }
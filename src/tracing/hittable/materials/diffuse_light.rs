@@ -0,0 +1,30 @@
+use crate::{Color, HitRecord, MaterialDescription, Ray, Scatter};
+
+/// Emits `emit` from the front face and absorbs everything that hits it.
+pub struct DiffuseLight {
+    emit: Color,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> DiffuseLight {
+        DiffuseLight { emit }
+    }
+}
+
+impl Scatter for DiffuseLight {
+    fn scatter(&self, _ray_in: &Ray, _hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        None
+    }
+
+    fn emitted(&self, hit_record: &HitRecord) -> Color {
+        if hit_record.front_face {
+            self.emit
+        } else {
+            Color::empty()
+        }
+    }
+
+    fn describe(&self) -> Option<MaterialDescription> {
+        Some(MaterialDescription::DiffuseLight { emit: self.emit })
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{Color, HitRecord, Ray, Scatter, Vec3};
+use crate::{Color, HitRecord, MaterialDescription, Ray, RayKind, Scatter, Vec3};
 
 pub struct Lambertian {
     albedo: Color,
@@ -16,8 +16,18 @@ impl Scatter for Lambertian {
         if direction.near_zero() {
             direction = hit_record.normal;
         }
-        let scattered = Ray::new(hit_record.p, direction);
+        let scattered = Ray::new(hit_record.p, direction).with_kind(RayKind::Diffuse);
 
-        Some((self.albedo, scattered))
+        Some((self.albedo(hit_record), scattered))
+    }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Color {
+        self.albedo * hit_record.vertex_color
+    }
+
+    fn describe(&self) -> Option<MaterialDescription> {
+        Some(MaterialDescription::Lambertian {
+            albedo: self.albedo,
+        })
     }
 }
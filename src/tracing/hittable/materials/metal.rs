@@ -1,4 +1,4 @@
-use crate::{Color, HitRecord, Ray, Scatter, Vec3};
+use crate::{Color, HitRecord, MaterialDescription, Ray, RayKind, Scatter, Vec3};
 
 pub struct Metal {
     albedo: Color,
@@ -21,12 +21,24 @@ impl Scatter for Metal {
         let scattered = Ray::new(
             hit_record.p,
             reflected + self.fuzz * Vec3::random_in_unit_sphere(),
-        );
+        )
+        .with_kind(RayKind::Specular);
 
         if scattered.direction().dot(&hit_record.normal) > 0.0 {
-            Some((self.albedo, scattered))
+            Some((self.albedo(hit_record), scattered))
         } else {
             None
         }
     }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Color {
+        self.albedo * hit_record.vertex_color
+    }
+
+    fn describe(&self) -> Option<MaterialDescription> {
+        Some(MaterialDescription::Metal {
+            albedo: self.albedo,
+            fuzz: self.fuzz,
+        })
+    }
 }
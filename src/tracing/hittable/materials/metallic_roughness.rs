@@ -0,0 +1,78 @@
+use std::sync::Arc;
+
+use rand::Rng;
+
+use crate::{Color, HitRecord, ImageTexture, Ray, RayKind, Scatter, Vec3};
+
+/// glTF's metallic-roughness material, approximated by picking a fuzzy
+/// metal reflection with probability `metallic` and a diffuse bounce
+/// otherwise.
+pub struct MetallicRoughness {
+    base_color: Color,
+    base_color_texture: Option<Arc<ImageTexture>>,
+    metallic: f64,
+    roughness: f64,
+    emissive: Color,
+}
+
+impl MetallicRoughness {
+    pub fn new(
+        base_color: Color,
+        base_color_texture: Option<Arc<ImageTexture>>,
+        metallic: f64,
+        roughness: f64,
+        emissive: Color,
+    ) -> MetallicRoughness {
+        MetallicRoughness {
+            base_color,
+            base_color_texture,
+            metallic: metallic.clamp(0.0, 1.0),
+            roughness: roughness.clamp(0.0, 1.0),
+            emissive,
+        }
+    }
+}
+
+impl Scatter for MetallicRoughness {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        let attenuation = self.albedo(hit_record);
+        if crate::rng().gen::<f64>() < self.metallic {
+            let reflected = ray_in.direction().reflect(&hit_record.normal).unit_vector();
+            let scattered = Ray::new(
+                hit_record.p,
+                reflected + self.roughness * Vec3::random_in_unit_sphere(),
+            )
+            .with_kind(RayKind::Specular);
+            if scattered.direction().dot(&hit_record.normal) > 0.0 {
+                Some((attenuation, scattered))
+            } else {
+                None
+            }
+        } else {
+            let mut direction = hit_record.normal + Vec3::random_unit_vector();
+            if direction.near_zero() {
+                direction = hit_record.normal;
+            }
+            Some((
+                attenuation,
+                Ray::new(hit_record.p, direction).with_kind(RayKind::Diffuse),
+            ))
+        }
+    }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Color {
+        let base_color = self.base_color * hit_record.vertex_color;
+        match &self.base_color_texture {
+            Some(texture) => base_color * texture.sample(hit_record.u, hit_record.v),
+            None => base_color,
+        }
+    }
+
+    fn emitted(&self, hit_record: &HitRecord) -> Color {
+        if hit_record.front_face {
+            self.emissive
+        } else {
+            Color::empty()
+        }
+    }
+}
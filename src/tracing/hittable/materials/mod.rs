@@ -4,3 +4,7 @@ pub mod metal;
 pub use metal::*;
 pub mod dielectric;
 pub use dielectric::*;
+pub mod diffuse_light;
+pub use diffuse_light::*;
+pub mod metallic_roughness;
+pub use metallic_roughness::*;
@@ -1,22 +1,43 @@
 pub mod sphere;
 pub use sphere::*;
+pub mod aabb;
+pub use aabb::*;
+pub mod triangle_mesh;
+pub use triangle_mesh::*;
 pub mod hittable_list;
 pub use hittable_list::*;
 pub mod hittable_map;
 pub use hittable_map::*;
+pub mod group;
+pub use group::*;
 pub mod hit_record;
 pub use hit_record::*;
 pub mod scatter;
 pub use scatter::*;
 pub mod materials;
 
-use crate::{Point3, Ray};
+use std::sync::Arc;
+
+use crate::{Background, Color, ObjectDescription, Point3, Ray};
 
 pub trait Hittable: Send + Sync {
     fn hit(&self, ray: &Ray, t_bounds: (f64, f64)) -> Option<HitRecord>;
+    fn background(&self, ray: &Ray) -> Color {
+        Background::default().color(ray)
+    }
 }
 
 pub trait Anchored: Hittable {
     fn origin(&self) -> Point3;
     fn set_origin(&mut self, origin: Point3);
+    /// Scene file representation, `None` if the object can't be exported.
+    ///
+    /// `material_name` registers the object's material with the exporter and
+    /// returns the name to reference it by.
+    fn describe(
+        &self,
+        _material_name: &mut dyn FnMut(&Arc<dyn Scatter>) -> Option<String>,
+    ) -> Option<ObjectDescription> {
+        None
+    }
 }
@@ -1,5 +1,17 @@
-use crate::{Color, HitRecord, Ray};
+use crate::{Color, HitRecord, MaterialDescription, Ray};
 
 pub trait Scatter: Send + Sync {
     fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)>;
+    /// Base color of the surface, used for the albedo AOV.
+    fn albedo(&self, _hit_record: &HitRecord) -> Color {
+        Color::new(1.0, 1.0, 1.0)
+    }
+    /// Light given off by the surface, black for everything but lights.
+    fn emitted(&self, _hit_record: &HitRecord) -> Color {
+        Color::empty()
+    }
+    /// Scene file representation, `None` if the material can't be exported.
+    fn describe(&self) -> Option<MaterialDescription> {
+        None
+    }
 }
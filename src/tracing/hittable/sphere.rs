@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use crate::{
     tracing::{ray::Ray, vec3::Point3},
-    Anchored, Scatter,
+    Anchored, ObjectDescription, Scatter,
 };
 
 use super::{HitRecord, Hittable};
@@ -46,7 +46,7 @@ impl Hittable for Sphere {
                     let hr = HitRecord::new(
                         p,
                         root,
-                        (p - &self.center) / self.radius,
+                        (p - self.center) / self.radius,
                         ray,
                         self.mat.clone(),
                     );
@@ -57,7 +57,7 @@ impl Hittable for Sphere {
                 let hr = HitRecord::new(
                     p,
                     root,
-                    (p - &self.center) / self.radius,
+                    (p - self.center) / self.radius,
                     ray,
                     self.mat.clone(),
                 );
@@ -75,4 +75,15 @@ impl Anchored for Sphere {
     fn set_origin(&mut self, origin: Point3) {
         self.center = origin;
     }
+
+    fn describe(
+        &self,
+        material_name: &mut dyn FnMut(&Arc<dyn Scatter>) -> Option<String>,
+    ) -> Option<ObjectDescription> {
+        Some(ObjectDescription::Sphere {
+            center: self.center,
+            radius: self.radius,
+            material: material_name(&self.mat)?,
+        })
+    }
 }
@@ -0,0 +1,294 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    load_mesh, Aabb, Anchored, Color, HitRecord, Hittable, MeshData, MeshError, ObjectDescription,
+    Point3, Ray, Scatter, Transform, Vec3,
+};
+
+const LEAF_SIZE: usize = 4;
+
+// Flattened BVH node, leaves reference `count` triangles starting at
+// `first` in `order`, inner nodes have their left child right after them
+// and the right child at `first`.
+#[derive(Debug, Clone)]
+struct BvhNode {
+    bounds: Aabb,
+    first: usize,
+    count: usize,
+}
+
+/// Indexed triangle mesh with its own bounding volume hierarchy.
+///
+/// The vertices are stored relative to the origin, the center of the
+/// bounding box when the mesh is created, so moving the mesh doesn't require
+/// rebuilding the hierarchy.
+pub struct TriangleMesh {
+    m_origin: Point3,
+    positions: Vec<Point3>,
+    normals: Option<Vec<Vec3>>,
+    uvs: Option<Vec<(f64, f64)>>,
+    colors: Option<Vec<Color>>,
+    indices: Vec<[usize; 3]>,
+    order: Vec<usize>,
+    nodes: Vec<BvhNode>,
+    materials: Vec<Arc<dyn Scatter>>,
+    // index into `materials` per triangle, empty when there's only one
+    triangle_materials: Vec<usize>,
+    // file and scale the mesh was loaded with, needed to export it
+    m_source: Option<(PathBuf, f64)>,
+}
+
+impl TriangleMesh {
+    /// `mesh` must have passed `MeshData::validate`.
+    pub fn new(mesh: MeshData, mat: Arc<dyn Scatter>) -> TriangleMesh {
+        TriangleMesh::with_materials(mesh, vec![mat], Vec::new())
+    }
+
+    /// Mesh whose triangles use different materials, `triangle_materials`
+    /// holds an index into `materials` for every triangle.
+    pub fn with_materials(
+        mesh: MeshData,
+        materials: Vec<Arc<dyn Scatter>>,
+        triangle_materials: Vec<usize>,
+    ) -> TriangleMesh {
+        assert!(!materials.is_empty(), "a mesh needs at least one material");
+        assert!(
+            triangle_materials.is_empty() || triangle_materials.len() == mesh.indices.len(),
+            "one material index per triangle"
+        );
+        let bounds = mesh
+            .positions
+            .iter()
+            .fold(Aabb::empty(), |bounds, p| bounds.grow(*p));
+        let origin = if mesh.positions.is_empty() {
+            Point3::empty()
+        } else {
+            bounds.center()
+        };
+        let mut triangle_mesh = TriangleMesh {
+            m_origin: origin,
+            positions: mesh.positions.iter().map(|p| *p - origin).collect(),
+            normals: mesh.normals,
+            uvs: mesh.uvs,
+            colors: mesh.colors,
+            order: (0..mesh.indices.len()).collect(),
+            indices: mesh.indices,
+            nodes: Vec::new(),
+            materials,
+            triangle_materials,
+            m_source: None,
+        };
+        if !triangle_mesh.indices.is_empty() {
+            let centroids: Vec<Point3> = (0..triangle_mesh.indices.len())
+                .map(|ix| triangle_mesh.triangle_bounds(ix).center())
+                .collect();
+            let count = triangle_mesh.order.len();
+            triangle_mesh.build(&centroids, 0, count);
+        }
+        triangle_mesh
+    }
+
+    /// Loads a PLY or STL file with its coordinates multiplied by `scale`.
+    /// Meshes loaded this way can be exported to scene files.
+    pub fn load<P: AsRef<Path>>(
+        path: P,
+        scale: f64,
+        mat: Arc<dyn Scatter>,
+    ) -> Result<TriangleMesh, MeshError> {
+        let path = path.as_ref();
+        let mut mesh = load_mesh(path)?;
+        if scale != 1.0 {
+            mesh = mesh.transformed(&Transform::scale(scale, scale, scale));
+        }
+        let mut triangle_mesh = TriangleMesh::new(mesh, mat);
+        // absolute, so exported scenes find the file wherever they're written
+        let file = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        triangle_mesh.m_source = Some((file, scale));
+        Ok(triangle_mesh)
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// Bounds in world space.
+    pub fn bounds(&self) -> Aabb {
+        self.nodes.first().map_or(Aabb::empty(), |node| {
+            Aabb::new(
+                node.bounds.min + self.m_origin,
+                node.bounds.max + self.m_origin,
+            )
+        })
+    }
+
+    fn triangle_bounds(&self, ix: usize) -> Aabb {
+        self.indices[ix]
+            .iter()
+            .fold(Aabb::empty(), |bounds, v| bounds.grow(self.positions[*v]))
+    }
+
+    // Median split along the largest axis of the centroid bounds.
+    fn build(&mut self, centroids: &[Point3], first: usize, count: usize) -> usize {
+        let bounds = self.order[first..first + count]
+            .iter()
+            .fold(Aabb::empty(), |bounds, ix| {
+                bounds.union(&self.triangle_bounds(*ix))
+            });
+        let node = self.nodes.len();
+        self.nodes.push(BvhNode {
+            bounds,
+            first,
+            count,
+        });
+        if count <= LEAF_SIZE {
+            return node;
+        }
+
+        let centroid_bounds = self.order[first..first + count]
+            .iter()
+            .fold(Aabb::empty(), |bounds, ix| bounds.grow(centroids[*ix]));
+        let axis = centroid_bounds.largest_axis();
+        let half = count / 2;
+        self.order[first..first + count].select_nth_unstable_by(half, |a, b| {
+            centroids[*a]
+                .axis(axis)
+                .total_cmp(&centroids[*b].axis(axis))
+        });
+        self.build(centroids, first, half);
+        let right = self.build(centroids, first + half, count - half);
+        self.nodes[node].first = right;
+        self.nodes[node].count = 0;
+        node
+    }
+
+    // Möller-Trumbore, returns the distance and barycentric coordinates.
+    fn hit_triangle(&self, ray: &Ray, ix: usize, t_bounds: (f64, f64)) -> Option<(f64, f64, f64)> {
+        let [a, b, c] = self.indices[ix].map(|v| self.positions[v]);
+        let edge1 = b - a;
+        let edge2 = c - a;
+        let pvec = ray.direction().cross(&edge2);
+        let det = edge1.dot(&pvec);
+        if det.abs() < 1.0e-12 {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+        let tvec = ray.origin() - &a;
+        let u = tvec.dot(&pvec) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let qvec = tvec.cross(&edge1);
+        let v = ray.direction().dot(&qvec) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = edge2.dot(&qvec) * inv_det;
+        if t < t_bounds.0 || t > t_bounds.1 {
+            return None;
+        }
+        Some((t, u, v))
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, ray: &Ray, t_bounds: (f64, f64)) -> Option<HitRecord> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let local = Ray::new(ray.origin() - &self.m_origin, *ray.direction()).with_kind(ray.kind());
+
+        let mut closest: Option<(usize, f64, f64, f64)> = None;
+        let mut t_max = t_bounds.1;
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            let node_ix = node;
+            let node = &self.nodes[node_ix];
+            if !node.bounds.hit(&local, (t_bounds.0, t_max)) {
+                continue;
+            }
+            if node.count == 0 {
+                stack.push(node.first);
+                stack.push(node_ix + 1);
+                continue;
+            }
+            for ix in &self.order[node.first..node.first + node.count] {
+                if let Some((t, u, v)) = self.hit_triangle(&local, *ix, (t_bounds.0, t_max)) {
+                    t_max = t;
+                    closest = Some((*ix, t, u, v));
+                }
+            }
+        }
+
+        let (ix, t, u, v) = closest?;
+        let [a, b, c] = self.indices[ix];
+        let geometric = (self.positions[b] - self.positions[a])
+            .cross(&(self.positions[c] - self.positions[a]))
+            .unit_vector();
+        let outward_normal = match &self.normals {
+            Some(normals) => {
+                let shading =
+                    ((1.0 - u - v) * normals[a] + u * normals[b] + v * normals[c]).unit_vector();
+                // keep the shading normal on the side the winding says is outside
+                if shading.dot(&geometric) < 0.0 {
+                    -shading
+                } else {
+                    shading
+                }
+            }
+            None => geometric,
+        };
+        let material = self.triangle_materials.get(ix).copied().unwrap_or(0);
+        let mut hit_record = HitRecord::new(
+            ray.at(t),
+            t,
+            outward_normal,
+            ray,
+            self.materials[material].clone(),
+        );
+        let weights = [1.0 - u - v, u, v];
+        if let Some(uvs) = &self.uvs {
+            let (u, v) = weights
+                .iter()
+                .zip([a, b, c])
+                .fold((0.0, 0.0), |(u, v), (w, vertex)| {
+                    (u + w * uvs[vertex].0, v + w * uvs[vertex].1)
+                });
+            hit_record.u = u;
+            hit_record.v = v;
+        }
+        if let Some(colors) = &self.colors {
+            hit_record.vertex_color =
+                weights[0] * colors[a] + weights[1] * colors[b] + weights[2] * colors[c];
+        }
+        Some(hit_record)
+    }
+}
+
+impl Anchored for TriangleMesh {
+    fn origin(&self) -> Point3 {
+        self.m_origin
+    }
+
+    fn set_origin(&mut self, origin: Point3) {
+        self.m_origin = origin;
+    }
+
+    fn describe(
+        &self,
+        material_name: &mut dyn FnMut(&Arc<dyn Scatter>) -> Option<String>,
+    ) -> Option<ObjectDescription> {
+        let (file, scale) = self.m_source.as_ref()?;
+        if self.materials.len() > 1 {
+            return None;
+        }
+        Some(ObjectDescription::Mesh {
+            file: file.clone(),
+            scale: *scale,
+            position: Some(self.m_origin),
+            material: material_name(&self.materials[0])?,
+        })
+    }
+}
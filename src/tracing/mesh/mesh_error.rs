@@ -0,0 +1,29 @@
+use std::{fmt::Display, io, path::PathBuf};
+
+#[derive(Debug)]
+pub enum MeshError {
+    Io { path: PathBuf, source: io::Error },
+    UnsupportedFormat { path: PathBuf },
+    Parse { path: PathBuf, message: String },
+}
+
+impl Display for MeshError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MeshError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            MeshError::UnsupportedFormat { path } => {
+                write!(f, "{}: unsupported mesh file format", path.display())
+            }
+            MeshError::Parse { path, message } => write!(f, "{}: {}", path.display(), message),
+        }
+    }
+}
+
+impl std::error::Error for MeshError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MeshError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
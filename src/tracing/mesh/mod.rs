@@ -0,0 +1,125 @@
+pub mod mesh_error;
+pub use mesh_error::*;
+pub mod ply;
+pub use ply::*;
+pub mod stl;
+pub use stl::*;
+
+use std::{fs, path::Path};
+
+use crate::{Color, Point3, Transform, Vec3};
+
+/// Indexed triangle mesh as read from a file, before it's turned into a
+/// `TriangleMesh` hittable.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MeshData {
+    pub positions: Vec<Point3>,
+    /// Per vertex shading normals, if the file has them.
+    pub normals: Option<Vec<Vec3>>,
+    /// Per vertex texture coordinates, `v` pointing up the image.
+    pub uvs: Option<Vec<(f64, f64)>>,
+    /// Per vertex linear colors, multiplied into the material's albedo.
+    pub colors: Option<Vec<Color>>,
+    /// Counter clockwise seen from the front.
+    pub indices: Vec<[usize; 3]>,
+}
+
+impl MeshData {
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// Applies `transform` to the vertices, keeping the winding counter
+    /// clockwise when the transform mirrors the mesh.
+    pub fn transformed(&self, transform: &Transform) -> MeshData {
+        let mut indices = self.indices.clone();
+        if transform.swaps_handedness() {
+            for triangle in &mut indices {
+                triangle.swap(1, 2);
+            }
+        }
+        MeshData {
+            positions: self.positions.iter().map(|p| transform.point(*p)).collect(),
+            normals: self.normals.as_ref().map(|normals| {
+                normals
+                    .iter()
+                    .map(|n| transform.normal(*n).unit_vector())
+                    .collect()
+            }),
+            uvs: self.uvs.clone(),
+            colors: self.colors.clone(),
+            indices,
+        }
+    }
+
+    /// Checks that every index refers to a vertex and that the per vertex
+    /// attributes match the vertex count.
+    pub fn validate(&self) -> Result<(), String> {
+        let vertex_count = self.positions.len();
+        if let Some(ix) = self
+            .indices
+            .iter()
+            .flatten()
+            .find(|ix| **ix >= vertex_count)
+        {
+            return Err(format!(
+                "vertex index {} out of range, the mesh has {} vertices",
+                ix, vertex_count
+            ));
+        }
+        if let Some(normals) = &self.normals {
+            if normals.len() != vertex_count {
+                return Err(format!(
+                    "{} normals for {} vertices",
+                    normals.len(),
+                    vertex_count
+                ));
+            }
+        }
+        if let Some(uvs) = &self.uvs {
+            if uvs.len() != vertex_count {
+                return Err(format!(
+                    "{} texture coordinates for {} vertices",
+                    uvs.len(),
+                    vertex_count
+                ));
+            }
+        }
+        if let Some(colors) = &self.colors {
+            if colors.len() != vertex_count {
+                return Err(format!(
+                    "{} vertex colors for {} vertices",
+                    colors.len(),
+                    vertex_count
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Loads a mesh file, the format is picked from the extension.
+pub fn load_mesh<P: AsRef<Path>>(path: P) -> Result<MeshData, MeshError> {
+    let path = path.as_ref();
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase());
+    let parse = match extension.as_deref() {
+        Some("ply") => parse_ply,
+        Some("stl") => parse_stl,
+        _ => {
+            return Err(MeshError::UnsupportedFormat {
+                path: path.to_path_buf(),
+            })
+        }
+    };
+    let bytes = fs::read(path).map_err(|source| MeshError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    parse(&bytes).map_err(|message| MeshError::Parse {
+        path: path.to_path_buf(),
+        message,
+    })
+}
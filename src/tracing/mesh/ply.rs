@@ -0,0 +1,502 @@
+use crate::{srgb_to_linear, Color, MeshData, Point3, Vec3};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Encoding {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum ScalarType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Result<ScalarType, String> {
+        match name {
+            "char" | "int8" => Ok(ScalarType::I8),
+            "uchar" | "uint8" => Ok(ScalarType::U8),
+            "short" | "int16" => Ok(ScalarType::I16),
+            "ushort" | "uint16" => Ok(ScalarType::U16),
+            "int" | "int32" => Ok(ScalarType::I32),
+            "uint" | "uint32" => Ok(ScalarType::U32),
+            "float" | "float32" => Ok(ScalarType::F32),
+            "double" | "float64" => Ok(ScalarType::F64),
+            _ => Err(format!("unknown property type `{}`", name)),
+        }
+    }
+    fn size(&self) -> usize {
+        match self {
+            ScalarType::I8 | ScalarType::U8 => 1,
+            ScalarType::I16 | ScalarType::U16 => 2,
+            ScalarType::I32 | ScalarType::U32 | ScalarType::F32 => 4,
+            ScalarType::F64 => 8,
+        }
+    }
+    // largest value of an integer type, `None` for floating point
+    fn max(&self) -> Option<f64> {
+        match self {
+            ScalarType::I8 => Some(i8::MAX as f64),
+            ScalarType::U8 => Some(u8::MAX as f64),
+            ScalarType::I16 => Some(i16::MAX as f64),
+            ScalarType::U16 => Some(u16::MAX as f64),
+            ScalarType::I32 => Some(i32::MAX as f64),
+            ScalarType::U32 => Some(u32::MAX as f64),
+            ScalarType::F32 | ScalarType::F64 => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Property {
+    Scalar {
+        name: String,
+        ty: ScalarType,
+    },
+    List {
+        name: String,
+        count: ScalarType,
+        item: ScalarType,
+    },
+}
+
+impl Property {
+    fn name(&self) -> &str {
+        match self {
+            Property::Scalar { name, .. } | Property::List { name, .. } => name,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    // Positions of the first set of alternative property names that is fully present
+    fn columns<const N: usize>(&self, alternatives: &[[&str; N]]) -> Option<[usize; N]> {
+        alternatives.iter().find_map(|names| {
+            let found = names.map(|name| self.properties.iter().position(|p| p.name() == name));
+            found
+                .iter()
+                .all(Option::is_some)
+                .then(|| found.map(Option::unwrap))
+        })
+    }
+}
+
+struct Header {
+    encoding: Encoding,
+    elements: Vec<Element>,
+    body_offset: usize,
+}
+
+fn parse_header(bytes: &[u8]) -> Result<Header, String> {
+    if !bytes.starts_with(b"ply") {
+        return Err("not a PLY file, missing `ply` magic".to_string());
+    }
+    let end = find(bytes, b"end_header").ok_or_else(|| "missing `end_header`".to_string())?;
+    let mut body_offset = end + b"end_header".len();
+    // the header ends with the first newline after `end_header`
+    if bytes.get(body_offset) == Some(&b'\r') {
+        body_offset += 1;
+    }
+    if bytes.get(body_offset) == Some(&b'\n') {
+        body_offset += 1;
+    }
+    let header = std::str::from_utf8(&bytes[..end]).map_err(|err| err.to_string())?;
+
+    let mut encoding = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for (ix, line) in header.lines().enumerate().skip(1) {
+        let line_error = |message: String| format!("line {}: {}", ix + 1, message);
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words[..] {
+            [] | ["comment", ..] | ["obj_info", ..] => {}
+            ["format", format, _version] => {
+                encoding = Some(match format {
+                    "ascii" => Encoding::Ascii,
+                    "binary_little_endian" => Encoding::BinaryLittleEndian,
+                    "binary_big_endian" => Encoding::BinaryBigEndian,
+                    _ => return Err(line_error(format!("unknown format `{}`", format))),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| line_error(format!("invalid element count `{}`", count)))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| line_error("property before any element".to_string()))?;
+                element.properties.push(Property::List {
+                    name: name.to_string(),
+                    count: ScalarType::parse(count).map_err(line_error)?,
+                    item: ScalarType::parse(item).map_err(line_error)?,
+                });
+            }
+            ["property", ty, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| line_error("property before any element".to_string()))?;
+                element.properties.push(Property::Scalar {
+                    name: name.to_string(),
+                    ty: ScalarType::parse(ty).map_err(line_error)?,
+                });
+            }
+            _ => return Err(line_error(format!("unexpected header line `{}`", line))),
+        }
+    }
+    Ok(Header {
+        encoding: encoding.ok_or_else(|| "missing `format` line".to_string())?,
+        elements,
+        body_offset,
+    })
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Reads the values of the body one at a time, whatever the encoding.
+struct BodyReader<'a> {
+    encoding: Encoding,
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> BodyReader<'a> {
+    fn read(&mut self, ty: ScalarType) -> Result<f64, String> {
+        if self.encoding == Encoding::Ascii {
+            let rest = &self.bytes[self.offset..];
+            let start = rest
+                .iter()
+                .position(|b| !b.is_ascii_whitespace())
+                .ok_or_else(|| "unexpected end of file".to_string())?;
+            let len = rest[start..]
+                .iter()
+                .position(|b| b.is_ascii_whitespace())
+                .unwrap_or(rest.len() - start);
+            self.offset += start + len;
+            let token = std::str::from_utf8(&rest[start..start + len]).unwrap_or_default();
+            return token
+                .parse::<f64>()
+                .map_err(|_| format!("invalid number `{}`", token));
+        }
+
+        let size = ty.size();
+        let raw = self
+            .bytes
+            .get(self.offset..self.offset + size)
+            .ok_or_else(|| "unexpected end of file".to_string())?;
+        self.offset += size;
+        let mut buf = [0u8; 8];
+        buf[..size].copy_from_slice(raw);
+        if self.encoding == Encoding::BinaryBigEndian {
+            buf[..size].reverse();
+        }
+        Ok(match ty {
+            ScalarType::I8 => buf[0] as i8 as f64,
+            ScalarType::U8 => buf[0] as f64,
+            ScalarType::I16 => i16::from_le_bytes([buf[0], buf[1]]) as f64,
+            ScalarType::U16 => u16::from_le_bytes([buf[0], buf[1]]) as f64,
+            ScalarType::I32 => i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            ScalarType::U32 => u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            ScalarType::F32 => f32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            ScalarType::F64 => f64::from_le_bytes(buf),
+        })
+    }
+}
+
+/// Parses an ascii or binary PLY file. Polygons with more than three
+/// vertices are split into triangle fans.
+///
+/// Besides positions, the `nx`/`ny`/`nz` normals, `u`/`v` (or `s`/`t`)
+/// texture coordinates and `red`/`green`/`blue` vertex colors are read when
+/// present. Integer colors are taken to be sRGB encoded.
+pub fn parse_ply(bytes: &[u8]) -> Result<MeshData, String> {
+    let header = parse_header(bytes)?;
+    let mut reader = BodyReader {
+        encoding: header.encoding,
+        bytes,
+        offset: header.body_offset,
+    };
+
+    let mut mesh = MeshData::default();
+    for element in &header.elements {
+        let face_indices = element
+            .properties
+            .iter()
+            .position(|p| p.name() == "vertex_indices" || p.name() == "vertex_index");
+        let is_vertex = element.name == "vertex";
+        let xyz = element.columns(&[["x", "y", "z"]]);
+        if is_vertex && xyz.is_none() {
+            return Err("vertex element without x, y and z properties".to_string());
+        }
+        let normal = element.columns(&[["nx", "ny", "nz"]]);
+        let uv = element.columns(&[
+            ["u", "v"],
+            ["s", "t"],
+            ["texture_u", "texture_v"],
+            ["texture_s", "texture_t"],
+        ]);
+        let color = element.columns(&[["red", "green", "blue"], ["r", "g", "b"]]);
+        // integer colors are sRGB scaled to the type's range, floating point
+        // colors are linear already
+        let color_scale = color.map(|columns| {
+            columns.map(|ix| match element.properties[ix] {
+                Property::Scalar { ty, .. } => ty.max(),
+                Property::List { .. } => None,
+            })
+        });
+        // counts come from the header, they aren't trusted to reserve memory
+        if is_vertex {
+            mesh.normals = normal.map(|_| Vec::new());
+            mesh.uvs = uv.map(|_| Vec::new());
+            mesh.colors = color.map(|_| Vec::new());
+        }
+        if element.properties.is_empty() {
+            continue;
+        }
+
+        let mut scalars = vec![0.0; element.properties.len()];
+        let mut list = Vec::new();
+        for _ in 0..element.count {
+            for (ix, property) in element.properties.iter().enumerate() {
+                match *property {
+                    Property::Scalar { ty, .. } => scalars[ix] = reader.read(ty)?,
+                    Property::List { count, item, .. } => {
+                        let count = reader.read(count)? as usize;
+                        let keep = Some(ix) == face_indices;
+                        if keep {
+                            list.clear();
+                        }
+                        for _ in 0..count {
+                            let value = reader.read(item)?;
+                            if keep {
+                                if value < 0.0 {
+                                    return Err(format!("negative vertex index {}", value));
+                                }
+                                list.push(value as usize);
+                            }
+                        }
+                    }
+                }
+            }
+            match element.name.as_str() {
+                "vertex" => {
+                    let [x, y, z] = xyz.unwrap().map(|ix| scalars[ix]);
+                    mesh.positions.push(Point3::new(x, y, z));
+                    if let (Some(normals), Some(columns)) = (&mut mesh.normals, normal) {
+                        let [x, y, z] = columns.map(|ix| scalars[ix]);
+                        normals.push(Vec3::new(x, y, z));
+                    }
+                    if let (Some(uvs), Some([u, v])) = (&mut mesh.uvs, uv) {
+                        uvs.push((scalars[u], scalars[v]));
+                    }
+                    if let (Some(colors), Some(columns), Some(scale)) =
+                        (&mut mesh.colors, color, color_scale)
+                    {
+                        let [r, g, b] = [0, 1, 2].map(|channel| {
+                            let value = scalars[columns[channel]];
+                            match scale[channel] {
+                                Some(max) => srgb_to_linear((value / max).max(0.0)),
+                                None => value,
+                            }
+                        });
+                        colors.push(Color::new(r, g, b));
+                    }
+                }
+                "face" if face_indices.is_some() => {
+                    for k in 1..list.len().saturating_sub(1) {
+                        mesh.indices.push([list[0], list[k], list[k + 1]]);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    mesh.validate()?;
+    Ok(mesh)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ASCII_QUAD: &str = "ply
+format ascii 1.0
+comment a unit quad
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 255 0 0
+1 0 0 0 255 0
+1 1 0 0 0 255
+0 1 0 255 255 255
+4 0 1 2 3
+";
+
+    #[test]
+    fn parses_ascii_headers() {
+        let header = parse_header(ASCII_QUAD.as_bytes()).unwrap();
+        assert_eq!(header.encoding, Encoding::Ascii);
+        let elements: Vec<(&str, usize, usize)> = header
+            .elements
+            .iter()
+            .map(|element| {
+                (
+                    element.name.as_str(),
+                    element.count,
+                    element.properties.len(),
+                )
+            })
+            .collect();
+        assert_eq!(elements, vec![("vertex", 4, 6), ("face", 1, 1)]);
+        assert!(matches!(
+            header.elements[1].properties[0],
+            Property::List {
+                count: ScalarType::U8,
+                item: ScalarType::I32,
+                ..
+            }
+        ));
+        assert!(ASCII_QUAD[header.body_offset..].starts_with("0 0 0 255"));
+    }
+
+    #[test]
+    fn rejects_broken_headers() {
+        let error = |header: &str| parse_header(header.as_bytes()).err().unwrap();
+        assert!(error("obj\nend_header\n").contains("magic"));
+        assert!(error("ply\nformat ascii 1.0\n").contains("end_header"));
+        assert_eq!(
+            error("ply\nformat utf8 1.0\nend_header\n"),
+            "line 2: unknown format `utf8`"
+        );
+        assert_eq!(
+            error("ply\nformat ascii 1.0\nproperty float x\nend_header\n"),
+            "line 3: property before any element"
+        );
+        assert_eq!(
+            error("ply\nformat ascii 1.0\nelement vertex 1\nproperty half x\nend_header\n"),
+            "line 4: unknown property type `half`"
+        );
+        assert_eq!(
+            error("ply\nelement vertex 0\nend_header\n"),
+            "missing `format` line"
+        );
+    }
+
+    #[test]
+    fn reads_ascii_meshes_and_splits_polygons() {
+        let mesh = parse_ply(ASCII_QUAD.as_bytes()).unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.positions[2], Point3::new(1.0, 1.0, 0.0));
+        assert_eq!(mesh.indices, vec![[0, 1, 2], [0, 2, 3]]);
+        let colors = mesh.colors.unwrap();
+        assert_eq!(colors[0], Color::new(1.0, 0.0, 0.0));
+        assert_eq!(colors[3], Color::new(1.0, 1.0, 1.0));
+        assert!(mesh.normals.is_none() && mesh.uvs.is_none());
+    }
+
+    // one triangle with a color of every integer type and a float one
+    fn binary_triangle(format: &str, newline: &str, to_bytes: fn(&[u8]) -> Vec<u8>) -> Vec<u8> {
+        let mut bytes = format!(
+            "ply{nl}format {} 1.0{nl}element vertex 3{nl}\
+             property float x{nl}property float y{nl}property float z{nl}\
+             property ushort red{nl}property short green{nl}property float blue{nl}\
+             element face 1{nl}property list uchar uint vertex_indices{nl}end_header{nl}",
+            format,
+            nl = newline
+        )
+        .into_bytes();
+        let corners = [[0.0f32, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+        for (ix, corner) in corners.iter().enumerate() {
+            for value in corner {
+                bytes.extend(to_bytes(&value.to_le_bytes()));
+            }
+            let full = ix == 0;
+            bytes.extend(to_bytes(&(if full { u16::MAX } else { 0 }).to_le_bytes()));
+            bytes.extend(to_bytes(&(if full { i16::MAX } else { -5 }).to_le_bytes()));
+            bytes.extend(to_bytes(&0.25f32.to_le_bytes()));
+        }
+        bytes.push(3);
+        for index in [0u32, 1, 2] {
+            bytes.extend(to_bytes(&index.to_le_bytes()));
+        }
+        bytes
+    }
+
+    #[test]
+    fn reads_binary_little_endian() {
+        let bytes = binary_triangle("binary_little_endian", "\n", |bytes| bytes.to_vec());
+        let mesh = parse_ply(&bytes).unwrap();
+        assert_eq!(mesh.positions[1], Point3::new(1.0, 0.0, 0.0));
+        assert_eq!(mesh.indices, vec![[0, 1, 2]]);
+        let colors = mesh.colors.unwrap();
+        // integers are normalized by their type's maximum, floats kept
+        assert_eq!(colors[0], Color::new(1.0, 1.0, 0.25));
+        assert_eq!(colors[1], Color::new(0.0, 0.0, 0.25));
+    }
+
+    #[test]
+    fn reads_binary_big_endian_after_crlf_headers() {
+        let big_endian = |bytes: &[u8]| bytes.iter().rev().copied().collect();
+        let bytes = binary_triangle("binary_big_endian", "\r\n", big_endian);
+        let header = parse_header(&bytes).unwrap();
+        assert_eq!(header.encoding, Encoding::BinaryBigEndian);
+        assert_eq!(&bytes[header.body_offset - 2..header.body_offset], b"\r\n");
+        let mesh = parse_ply(&bytes).unwrap();
+        assert_eq!(mesh.positions[2], Point3::new(0.0, 1.0, 0.0));
+        assert_eq!(mesh.indices, vec![[0, 1, 2]]);
+    }
+
+    #[test]
+    fn rejects_huge_declared_counts() {
+        let header = "ply\nformat binary_little_endian 1.0\nelement vertex 1000000000000000000\n\
+                      property float x\nproperty float y\nproperty float z\nproperty float nx\n\
+                      property float ny\nproperty float nz\nproperty uchar red\n\
+                      property uchar green\nproperty uchar blue\nend_header\n";
+        let mut bytes = header.as_bytes().to_vec();
+        bytes.extend([0; 27]);
+        let error = parse_ply(&bytes).err().unwrap();
+        assert_eq!(error, "unexpected end of file");
+    }
+
+    #[test]
+    fn rejects_negative_vertex_indices() {
+        let source = ASCII_QUAD.replace("4 0 1 2 3", "3 0 -1 2");
+        let error = parse_ply(source.as_bytes()).err().unwrap();
+        assert_eq!(error, "negative vertex index -1");
+    }
+
+    #[test]
+    fn rejects_truncated_binary_bodies() {
+        let bytes = binary_triangle("binary_little_endian", "\n", |bytes| bytes.to_vec());
+        let error = parse_ply(&bytes[..bytes.len() - 1]).err().unwrap();
+        assert_eq!(error, "unexpected end of file");
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+
+use crate::{MeshData, Point3, Vec3};
+
+/// Parses an ascii or binary STL file.
+///
+/// Identical vertices of neighbouring facets are merged, and facets whose
+/// winding disagrees with their stored normal are flipped.
+pub fn parse_stl(bytes: &[u8]) -> Result<MeshData, String> {
+    // binary files may start with `solid` too, the size is the reliable tell
+    let binary_size = bytes
+        .get(80..84)
+        .map(|count| 84 + 50 * u32::from_le_bytes(count.try_into().unwrap()) as usize);
+    let mut builder = StlBuilder::default();
+    if binary_size == Some(bytes.len()) || !bytes.trim_ascii_start().starts_with(b"solid") {
+        parse_binary(bytes, &mut builder)?;
+    } else {
+        parse_ascii(bytes, &mut builder)?;
+    }
+    Ok(builder.mesh)
+}
+
+#[derive(Default)]
+struct StlBuilder {
+    mesh: MeshData,
+    vertices: HashMap<[u64; 3], usize>,
+}
+
+impl StlBuilder {
+    fn vertex(&mut self, p: Point3) -> usize {
+        let key = [p.x().to_bits(), p.y().to_bits(), p.z().to_bits()];
+        let positions = &mut self.mesh.positions;
+        *self.vertices.entry(key).or_insert_with(|| {
+            positions.push(p);
+            positions.len() - 1
+        })
+    }
+
+    fn facet(&mut self, normal: Vec3, corners: &[Point3]) {
+        for k in 1..corners.len().saturating_sub(1) {
+            let (a, mut b, mut c) = (corners[0], corners[k], corners[k + 1]);
+            if (b - a).cross(&(c - a)).dot(&normal) < 0.0 {
+                std::mem::swap(&mut b, &mut c);
+            }
+            let triangle = [self.vertex(a), self.vertex(b), self.vertex(c)];
+            self.mesh.indices.push(triangle);
+        }
+    }
+}
+
+fn parse_binary(bytes: &[u8], builder: &mut StlBuilder) -> Result<(), String> {
+    let count = bytes
+        .get(80..84)
+        .map(|count| u32::from_le_bytes(count.try_into().unwrap()) as usize)
+        .ok_or_else(|| "binary STL shorter than its header".to_string())?;
+    let facets = &bytes[84..];
+    if facets.len() < 50 * count {
+        return Err(format!(
+            "binary STL declares {} triangles but holds only {}",
+            count,
+            facets.len() / 50
+        ));
+    }
+    let vector = |chunk: &[u8]| {
+        let [x, y, z] = [0, 4, 8]
+            .map(|offset| f32::from_le_bytes(chunk[offset..offset + 4].try_into().unwrap()) as f64);
+        Vec3::new(x, y, z)
+    };
+    for facet in facets.chunks_exact(50).take(count) {
+        let normal = vector(&facet[0..12]);
+        let corners = [
+            vector(&facet[12..24]),
+            vector(&facet[24..36]),
+            vector(&facet[36..48]),
+        ];
+        builder.facet(normal, &corners);
+    }
+    Ok(())
+}
+
+fn parse_ascii(bytes: &[u8], builder: &mut StlBuilder) -> Result<(), String> {
+    let source = std::str::from_utf8(bytes).map_err(|err| err.to_string())?;
+    let mut normal = Vec3::empty();
+    let mut corners = Vec::new();
+    for (ix, line) in source.lines().enumerate() {
+        let line_error = |message: String| format!("line {}: {}", ix + 1, message);
+        let mut words = line.split_whitespace();
+        let numbers = |words: std::str::SplitWhitespace| {
+            let values = words
+                .map(str::parse::<f64>)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|err| line_error(err.to_string()))?;
+            match values[..] {
+                [x, y, z] => Ok(Vec3::new(x, y, z)),
+                _ => Err(line_error(format!(
+                    "expected 3 numbers, got {}",
+                    values.len()
+                ))),
+            }
+        };
+        match words.next() {
+            Some("facet") => {
+                corners.clear();
+                normal = match words.next() {
+                    Some("normal") => numbers(words)?,
+                    _ => Vec3::empty(),
+                };
+            }
+            Some("vertex") => corners.push(numbers(words)?),
+            Some("endfacet") => {
+                builder.facet(normal, &corners);
+                corners.clear();
+            }
+            Some("solid" | "endsolid" | "outer" | "endloop") | None => {}
+            Some(word) => return Err(line_error(format!("unexpected `{}`", word))),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a unit quad in two facets, the second wound against its normal
+    const ASCII_QUAD: &str = "solid quad
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 1 1 0
+    endloop
+  endfacet
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 0 1 0
+      vertex 1 1 0
+    endloop
+  endfacet
+endsolid quad
+";
+
+    fn binary(header: &[u8], facets: &[[[f32; 3]; 4]]) -> Vec<u8> {
+        let mut bytes = header.to_vec();
+        bytes.resize(80, 0);
+        bytes.extend((facets.len() as u32).to_le_bytes());
+        for facet in facets {
+            for value in facet.iter().flatten() {
+                bytes.extend(value.to_le_bytes());
+            }
+            bytes.extend([0, 0]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn reads_ascii_and_merges_shared_vertices() {
+        let mesh = parse_stl(ASCII_QUAD.as_bytes()).unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.indices, vec![[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(mesh.positions[3], Point3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn rejects_malformed_ascii() {
+        let error = parse_stl(b"solid x\nfacet normal 0 0 1\nvertex 0 0\n").err();
+        assert_eq!(error.unwrap(), "line 3: expected 3 numbers, got 2");
+        let error = parse_stl(b"solid x\nfacet normal 0 0 1\nvertex 0 0 a\n").err();
+        assert!(error.unwrap().starts_with("line 3: "));
+        let error = parse_stl(b"solid x\nface normal 0 0 1\n").err();
+        assert_eq!(error.unwrap(), "line 2: unexpected `face`");
+    }
+
+    #[test]
+    fn reads_binary() {
+        let facet = [
+            [0.0, 0.0, -1.0],
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ];
+        let mesh = parse_stl(&binary(b"binary", &[facet])).unwrap();
+        assert_eq!(mesh.positions.len(), 3);
+        // flipped to face along the stored normal
+        let [a, b, c] = mesh.indices[0].map(|ix| mesh.positions[ix]);
+        assert_eq!((b - a).cross(&(c - a)), Vec3::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn tells_binary_files_starting_with_solid_by_size() {
+        let facet = [
+            [0.0, 0.0, 1.0],
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ];
+        let mesh = parse_stl(&binary(b"solid exported", &[facet, facet])).unwrap();
+        assert_eq!(mesh.positions.len(), 3);
+        assert_eq!(mesh.indices, vec![[0, 1, 2], [0, 1, 2]]);
+    }
+
+    #[test]
+    fn rejects_truncated_binary() {
+        let mut bytes = binary(b"binary", &[[[0.0; 3]; 4]]);
+        bytes[80] = 2;
+        let error = parse_stl(&bytes).err().unwrap();
+        assert_eq!(error, "binary STL declares 2 triangles but holds only 1");
+    }
+}
@@ -1,3 +1,5 @@
+pub mod random;
+pub use random::*;
 pub mod vec3;
 pub use vec3::*;
 pub mod color;
@@ -8,3 +10,31 @@ pub mod ray;
 pub use ray::*;
 pub mod camera;
 pub use camera::*;
+pub mod framebuffer;
+pub use framebuffer::*;
+pub mod tonemap;
+pub use tonemap::*;
+pub mod filter;
+pub use filter::*;
+pub mod film;
+pub use film::*;
+pub mod region;
+pub use region::*;
+pub mod aov;
+pub use aov::*;
+pub mod denoise;
+pub use denoise::*;
+pub mod background;
+pub use background::*;
+pub mod scene;
+pub use scene::*;
+pub mod transform;
+pub use transform::*;
+pub mod mesh;
+pub use mesh::*;
+pub mod texture;
+pub use texture::*;
+pub mod pick;
+pub use pick::*;
+pub mod path_debug;
+pub use path_debug::*;
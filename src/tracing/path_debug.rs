@@ -0,0 +1,181 @@
+use std::sync::Arc;
+
+use rand::Rng;
+use serde::Serialize;
+
+use crate::{
+    build_camera, rng, seed_row, Color, Hittable, HittableMap, MaterialDescription, Point3, Ray,
+    RayKind, SceneConfig, Vec3,
+};
+
+/// Every bounce of one camera sample, see `trace_path`.
+#[derive(Debug, Clone, Serialize)]
+pub struct PathTrace {
+    pub pixel: (u32, u32),
+    pub sample: u32,
+    pub seed: u64,
+    /// What `ray_color` returned for the sample.
+    pub color: Color,
+    pub bounces: Vec<Bounce>,
+    pub termination: Termination,
+}
+
+/// One ray of the path and what it hit.
+#[derive(Debug, Clone, Serialize)]
+pub struct Bounce {
+    pub origin: Point3,
+    pub direction: Vec3,
+    pub kind: RayKind,
+    /// `None` when the ray escaped, the rest of the hit fields are then
+    /// missing too.
+    pub object: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub point: Option<Point3>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normal: Option<Vec3>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub front_face: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distance: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub material: Option<MaterialDescription>,
+    /// Light emitted at the hit, or the background for escaped rays.
+    pub emitted: Color,
+    /// Set when the material scattered the ray.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attenuation: Option<Color>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scattered: Option<Vec3>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Termination {
+    /// The last ray hit nothing and picked up the background.
+    Escaped,
+    /// The material at the last hit didn't scatter, e.g. a light or a metal
+    /// reflection below the surface.
+    Absorbed,
+    /// `max_depth` bounces were traced.
+    MaxDepth,
+    /// The camera sees nothing at this pixel, e.g. outside a fisheye's
+    /// image circle.
+    NoCameraRay,
+}
+
+/// Traces sample `sample` of pixel `x`, `y` (from the top left corner) and
+/// records every bounce.
+///
+/// The random generator is replayed the way `render_passes` draws from it,
+/// so with the same seed the path is exactly the one that went into a
+/// render. Scenes without a seed are traced with seed 0. Returns `None` when
+/// the pixel or sample is outside the image or `samples_per_pixel`.
+pub fn trace_path(
+    world: &Arc<HittableMap>,
+    config: &SceneConfig,
+    x: u32,
+    y: u32,
+    sample: u32,
+) -> Option<PathTrace> {
+    if x >= config.image_width || y >= config.image_height || sample >= config.samples_per_pixel {
+        return None;
+    }
+    let seed = config.seed.unwrap_or(0);
+    let camera = build_camera(config);
+    let width = config.image_width;
+    let height = config.image_height;
+    let j = height - 1 - y;
+
+    // a region render starts its rows at the region
+    let first = match &config.region {
+        Some(region) if region.contains(x, y) => region.x,
+        _ => 0,
+    };
+
+    seed_row(seed, y);
+    let mut rng = rng();
+    for i in first..=x {
+        for s in 0..config.samples_per_pixel {
+            let u = (i as f64 + rng.gen::<f64>()) / (width - 1) as f64;
+            let v = (j as f64 + rng.gen::<f64>()) / (height - 1) as f64;
+            let ray = camera.get_ray(u, v);
+            if (i, s) == (x, sample) {
+                let mut bounces = Vec::new();
+                let (color, termination) = match ray {
+                    Some(ray) => record(&ray, world, config.max_depth, &mut bounces),
+                    None => (Color::empty(), Termination::NoCameraRay),
+                };
+                return Some(PathTrace {
+                    pixel: (x, y),
+                    sample,
+                    seed,
+                    color,
+                    bounces,
+                    termination,
+                });
+            }
+            if let Some(ray) = ray {
+                ray.ray_color(world, config.max_depth);
+            }
+        }
+    }
+    unreachable!("the loops reach pixel {} sample {}", x, sample)
+}
+
+// `Ray::ray_color` with every bounce written to `bounces`, it must draw the
+// same random numbers in the same order.
+fn record(
+    ray: &Ray,
+    world: &HittableMap,
+    depth: u32,
+    bounces: &mut Vec<Bounce>,
+) -> (Color, Termination) {
+    if depth == 0 {
+        return (Color::empty(), Termination::MaxDepth);
+    }
+    let mut bounce = Bounce {
+        origin: *ray.origin(),
+        direction: *ray.direction(),
+        kind: ray.kind(),
+        object: None,
+        point: None,
+        normal: None,
+        front_face: None,
+        distance: None,
+        material: None,
+        emitted: Color::empty(),
+        attenuation: None,
+        scattered: None,
+    };
+    let Some(hit_record) = world.hit(ray, (0.001, f64::INFINITY)) else {
+        let background = world.background(ray);
+        bounce.emitted = background;
+        bounces.push(bounce);
+        return (background, Termination::Escaped);
+    };
+    bounce.object = hit_record
+        .object_id
+        .and_then(|id| world.name_of(id))
+        .map(str::to_string);
+    bounce.point = Some(hit_record.p);
+    bounce.normal = Some(hit_record.normal);
+    bounce.front_face = Some(hit_record.front_face);
+    bounce.distance = Some(hit_record.t * ray.direction().length());
+    bounce.material = hit_record.mat.describe();
+    bounce.emitted = hit_record.mat.emitted(&hit_record);
+
+    let emitted = bounce.emitted;
+    match hit_record.mat.scatter(ray, &hit_record) {
+        Some((attenuation, scattered)) => {
+            bounce.attenuation = Some(attenuation);
+            bounce.scattered = Some(*scattered.direction());
+            bounces.push(bounce);
+            let (incoming, termination) = record(&scattered, world, depth - 1, bounces);
+            (emitted + attenuation * incoming, termination)
+        }
+        None => {
+            bounces.push(bounce);
+            (emitted, Termination::Absorbed)
+        }
+    }
+}
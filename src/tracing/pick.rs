@@ -0,0 +1,45 @@
+use serde::Serialize;
+
+use crate::{build_camera, Hittable, HittableMap, MaterialDescription, Point3, SceneConfig, Vec3};
+
+/// What the camera sees first through a pixel.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Pick {
+    /// Name of the object in the `HittableMap`.
+    pub name: String,
+    pub point: Point3,
+    /// Surface normal, facing the camera.
+    pub normal: Vec3,
+    /// Distance from the camera to `point`.
+    pub distance: f64,
+    /// `None` for materials that can't be described.
+    pub material: Option<MaterialDescription>,
+}
+
+/// Casts the primary ray through the center of pixel `x`, `y`, counted from
+/// the top left corner of the image like the rendered file.
+///
+/// The ray starts at the center of the lens, so the result doesn't depend on
+/// the aperture and is the same every time, also through a lens system.
+/// Returns `None` when the pixel is outside the image, the camera sees
+/// nothing there or the ray hits nothing.
+pub fn pick(world: &HittableMap, config: &SceneConfig, x: u32, y: u32) -> Option<Pick> {
+    if x >= config.image_width || y >= config.image_height {
+        return None;
+    }
+    let camera = build_camera(config);
+    // same mapping as `render_passes`, whose rows run bottom to top
+    let u = (x as f64 + 0.5) / (config.image_width - 1) as f64;
+    let v = ((config.image_height - 1 - y) as f64 + 0.5) / (config.image_height - 1) as f64;
+    let ray = camera.center_ray(u, v)?;
+
+    let hit_record = world.hit(&ray, (0.001, f64::INFINITY))?;
+    let name = world.name_of(hit_record.object_id?)?.to_string();
+    Some(Pick {
+        name,
+        point: hit_record.p,
+        normal: hit_record.normal,
+        distance: hit_record.t * ray.direction().length(),
+        material: hit_record.mat.describe(),
+    })
+}
@@ -0,0 +1,46 @@
+use std::cell::RefCell;
+
+use rand::{rngs::SmallRng, RngCore, SeedableRng};
+
+thread_local! {
+    static RNG: RefCell<SmallRng> = RefCell::new(SmallRng::from_entropy());
+}
+
+/// Handle to the per thread generator every random decision in the tracer goes
+/// through, a drop-in for `rand::thread_rng()` that can be reseeded.
+#[derive(Debug, Copy, Clone)]
+pub struct TracingRng;
+
+pub fn rng() -> TracingRng {
+    TracingRng
+}
+
+/// Reseeds the calling thread's generator, making everything it draws next
+/// reproducible.
+pub fn seed_rng(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = SmallRng::seed_from_u64(seed));
+}
+
+/// Seeds the calling thread for rendering image row `row` (from the top), so
+/// the result doesn't depend on which thread renders which row.
+pub fn seed_row(seed: u64, row: u32) {
+    seed_rng(seed ^ (row as u64).wrapping_mul(0x9e3779b97f4a7c15));
+}
+
+impl RngCore for TracingRng {
+    fn next_u32(&mut self) -> u32 {
+        RNG.with(|rng| rng.borrow_mut().next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        RNG.with(|rng| rng.borrow_mut().next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        RNG.with(|rng| rng.borrow_mut().fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        RNG.with(|rng| rng.borrow_mut().try_fill_bytes(dest))
+    }
+}
@@ -1,11 +1,26 @@
 use std::sync::Arc;
 
-use crate::{Color, Hittable, Point3, Vec3};
+use serde::Serialize;
+
+use crate::{AovSample, Color, Hittable, Point3, Vec3};
+
+/// What produced a ray, used to hide objects from some rays only.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RayKind {
+    /// Leaves the camera, or anything else that isn't a scattered ray.
+    Camera,
+    /// Scattered diffusely, these rays carry the light that objects shadow.
+    Diffuse,
+    /// Mirror reflection or refraction.
+    Specular,
+}
 
 #[derive(Debug, Clone)]
 pub struct Ray {
     m_orig: Point3,
     m_dir: Vec3,
+    m_kind: RayKind,
 }
 
 impl Ray {
@@ -13,12 +28,20 @@ impl Ray {
         Ray {
             m_orig: origin,
             m_dir: direction,
+            m_kind: RayKind::Camera,
+        }
+    }
+    pub fn with_kind(self, kind: RayKind) -> Ray {
+        Ray {
+            m_kind: kind,
+            ..self
         }
     }
     pub fn empty() -> Ray {
         Ray {
             m_orig: Point3::empty(),
             m_dir: Vec3::empty(),
+            m_kind: RayKind::Camera,
         }
     }
     pub fn origin(&self) -> &Point3 {
@@ -27,27 +50,58 @@ impl Ray {
     pub fn direction(&self) -> &Vec3 {
         &self.m_dir
     }
+    pub fn kind(&self) -> RayKind {
+        self.m_kind
+    }
     pub fn at(&self, t: f64) -> Point3 {
         self.m_orig + (t * self.m_dir)
     }
     pub fn ray_color(&self, world: &Arc<impl Hittable>, depth: u32) -> Color {
         if depth > 0 {
-            if let Some(hit_record) = world.hit(&self, (0.001, f64::INFINITY)) {
-                if let Some((attenuation, scattered)) = hit_record.mat.scatter(&self, &hit_record) {
-                    attenuation * scattered.ray_color(world, depth - 1)
+            if let Some(hit_record) = world.hit(self, (0.001, f64::INFINITY)) {
+                let emitted = hit_record.mat.emitted(&hit_record);
+                if let Some((attenuation, scattered)) = hit_record.mat.scatter(self, &hit_record) {
+                    emitted + attenuation * scattered.ray_color(world, depth - 1)
                 } else {
                     // let target = hit_record.p + hit_record.normal + Point3::random_unit_vector();
                     // let target = hit_record.p + Point3::random_in_hemisphere(&hit_record.normal);
                     // 0.5 * Ray::new(hit_record.p, target - hit_record.p).ray_color(world, depth - 1)
-                    Color::empty()
+                    emitted
                 }
             } else {
-                let unit_direction = self.direction().unit_vector();
-                let t = 0.5 * (unit_direction.y() + 1.0);
-                (1.0 - t) * Color::new(1.0, 1.0, 1.0) + t * Color::new(0.5, 0.7, 1.0)
+                world.background(self)
+            }
+        } else {
+            Color::empty()
+        }
+    }
+    /// Same as `ray_color`, additionally reporting what the ray hit first for the AOVs.
+    pub fn ray_color_aov(&self, world: &Arc<impl Hittable>, depth: u32) -> (Color, AovSample) {
+        if depth == 0 {
+            return (Color::empty(), AovSample::miss(Color::empty()));
+        }
+        if let Some(hit_record) = world.hit(self, (0.001, f64::INFINITY)) {
+            let aov = AovSample {
+                albedo: hit_record.mat.albedo(&hit_record),
+                normal: hit_record.normal,
+                depth: hit_record.t * self.direction().length(),
+                position: hit_record.p,
+                object_id: hit_record.object_id,
+                hit: true,
+                variance: 0.0,
+            };
+            let emitted = hit_record.mat.emitted(&hit_record);
+            if let Some((attenuation, scattered)) = hit_record.mat.scatter(self, &hit_record) {
+                (
+                    emitted + attenuation * scattered.ray_color(world, depth - 1),
+                    aov,
+                )
+            } else {
+                (emitted, aov)
             }
         } else {
-            return Color::empty();
+            let background = world.background(self);
+            (background, AovSample::miss(background))
         }
     }
 }
@@ -0,0 +1,106 @@
+use std::{ops::Range, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// Rectangle of the image to render, in pixels from the top left corner.
+/// The camera keeps the framing of the full image, but the noise differs
+/// from a full render with the same seed, as rows start drawing random
+/// numbers at the region.
+///
+/// ```toml
+/// [config.region]
+/// x = 120
+/// y = 40
+/// width = 64
+/// height = 48
+/// background = "render.png"
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RenderRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Write just the region instead of the full image.
+    #[serde(default)]
+    pub crop: bool,
+    /// Full size image the region is pasted into, e.g. an earlier render in
+    /// the same format as the output. Pixels outside the region are black
+    /// without it. Scene files resolve it relative to themselves.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<PathBuf>,
+}
+
+impl RenderRegion {
+    pub fn columns(&self) -> Range<u32> {
+        self.x..self.x.saturating_add(self.width)
+    }
+    pub fn rows(&self) -> Range<u32> {
+        self.y..self.y.saturating_add(self.height)
+    }
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        self.columns().contains(&x) && self.rows().contains(&y)
+    }
+    /// `true` when the region is non-empty and inside a `width` x `height`
+    /// image.
+    pub fn fits(&self, width: u32, height: u32) -> bool {
+        let inside = |start: u32, size: u32, end: u32| {
+            size > 0 && start.checked_add(size).is_some_and(|last| last <= end)
+        };
+        inside(self.x, self.width, width) && inside(self.y, self.height, height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(x: u32, y: u32, width: u32, height: u32) -> RenderRegion {
+        RenderRegion {
+            x,
+            y,
+            width,
+            height,
+            crop: false,
+            background: None,
+        }
+    }
+
+    #[test]
+    fn fits_regions_inside_the_image() {
+        assert!(region(0, 0, 200, 100).fits(200, 100));
+        assert!(region(10, 20, 30, 40).fits(200, 100));
+        // touching the right and bottom edges
+        assert!(region(199, 99, 1, 1).fits(200, 100));
+    }
+
+    #[test]
+    fn rejects_empty_and_overhanging_regions() {
+        assert!(!region(10, 10, 0, 5).fits(200, 100));
+        assert!(!region(10, 10, 5, 0).fits(200, 100));
+        assert!(!region(199, 0, 2, 1).fits(200, 100));
+        assert!(!region(0, 100, 1, 1).fits(200, 100));
+        assert!(!region(0, 0, 201, 100).fits(200, 100));
+    }
+
+    #[test]
+    fn rejects_regions_whose_end_overflows() {
+        assert!(!region(u32::MAX, 0, 2, 1).fits(u32::MAX, 1));
+        assert!(!region(0, 1, 1, u32::MAX).fits(1, u32::MAX));
+        // the ranges saturate instead of wrapping around
+        let overflowing = region(u32::MAX - 1, 0, 4, 1);
+        assert_eq!(overflowing.columns(), u32::MAX - 1..u32::MAX);
+        assert_eq!(overflowing.rows(), 0..1);
+    }
+
+    #[test]
+    fn contains_the_half_open_rectangle() {
+        let region = region(2, 3, 4, 5);
+        assert!(region.contains(2, 3));
+        assert!(region.contains(5, 7));
+        assert!(!region.contains(6, 3));
+        assert!(!region.contains(2, 8));
+        assert!(!region.contains(1, 3));
+    }
+}
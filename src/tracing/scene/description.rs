@@ -0,0 +1,273 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AovOutput, Background, Bokeh, Color, DenoiseSettings, Focus, Group, LensSystem, PhysicalCamera,
+    PixelFilter, Point3, Projection, RenderRegion, SceneConfig, Stereo, TiltShift, ToneMapping,
+    Vec3, Visibility,
+};
+
+/// Serde representation of a scene file.
+///
+/// ```toml
+/// [config]
+/// image_width = 400
+/// lookfrom = [13.0, 2.0, 3.0]
+///
+/// [materials.ground]
+/// type = "lambertian"
+/// albedo = [0.5, 0.5, 0.5]
+///
+/// [objects.ground]
+/// type = "sphere"
+/// center = [0.0, -1000.0, 0.0]
+/// radius = 1000.0
+/// material = "ground"
+///
+/// [groups.car]
+/// position = [0.0, 0.5, 0.0]
+///
+/// [objects."car/wheel_fl"]
+/// type = "sphere"
+/// center = [1.0, 0.0, 1.0]
+/// radius = 0.5
+/// material = "ground"
+/// ```
+///
+/// Objects and groups whose name contains `/` are placed relative to the
+/// group named by the part before the last `/`. A `[visibility."car/body"]`
+/// table with `camera`, `shadows` and `reflections` flags hides an object
+/// from some rays.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneDescription {
+    #[serde(default)]
+    pub config: ConfigDescription,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<Background>,
+    #[serde(default)]
+    pub materials: BTreeMap<String, MaterialDescription>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub groups: BTreeMap<String, GroupDescription>,
+    #[serde(default)]
+    pub objects: BTreeMap<String, ObjectDescription>,
+    /// Objects hidden from some rays, by object name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub visibility: BTreeMap<String, Visibility>,
+}
+
+/// `SceneConfig` with every field optional, missing fields fall back to
+/// `SceneConfig::default()`. A missing `image_height` is derived from the
+/// width and aspect ratio, a missing `aspect_ratio` from the width and height.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigDescription {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aspect_ratio: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_height: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub samples_per_pixel: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_depth: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lookfrom: Option<Point3>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lookat: Option<Point3>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vup: Option<Vec3>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vfov: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dist_to_focus: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aperature: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focus: Option<Focus>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub projection: Option<Projection>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bokeh: Option<Bokeh>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tilt_shift: Option<TiltShift>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lens: Option<LensSystem>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stereo: Option<Stereo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub physical: Option<PhysicalCamera>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num_threads: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tone_mapping: Option<ToneMapping>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<PixelFilter>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aov_output: Option<AovOutput>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub denoise: Option<DenoiseSettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<RenderRegion>,
+}
+
+impl ConfigDescription {
+    pub fn from_config(config: &SceneConfig) -> ConfigDescription {
+        ConfigDescription {
+            aspect_ratio: Some(config.aspect_ratio),
+            image_width: Some(config.image_width),
+            image_height: Some(config.image_height),
+            samples_per_pixel: Some(config.samples_per_pixel),
+            max_depth: Some(config.max_depth),
+            lookfrom: Some(config.lookfrom),
+            lookat: Some(config.lookat),
+            vup: Some(config.vup),
+            vfov: Some(config.vfov),
+            dist_to_focus: Some(config.dist_to_focus),
+            aperature: Some(config.aperature),
+            focus: Some(config.focus.clone()),
+            projection: Some(config.projection),
+            bokeh: Some(config.bokeh.clone()),
+            tilt_shift: Some(config.tilt_shift),
+            lens: config.lens.clone(),
+            stereo: config.stereo,
+            physical: config.physical,
+            num_threads: Some(config.num_threads),
+            tone_mapping: Some(config.tone_mapping),
+            filter: Some(config.filter),
+            aov_output: Some(config.aov_output),
+            denoise: config.denoise,
+            seed: config.seed,
+            region: config.region.clone(),
+        }
+    }
+
+    pub fn to_config(&self) -> SceneConfig {
+        let mut config = SceneConfig::default();
+        config.aspect_ratio = match (self.aspect_ratio, self.image_width, self.image_height) {
+            (Some(aspect_ratio), _, _) => aspect_ratio,
+            (None, Some(width), Some(height)) => width as f64 / height as f64,
+            _ => config.aspect_ratio,
+        };
+        config.image_width = self.image_width.unwrap_or(config.image_width);
+        config.image_height = self
+            .image_height
+            .unwrap_or((config.image_width as f64 / config.aspect_ratio) as u32);
+        config.samples_per_pixel = self.samples_per_pixel.unwrap_or(config.samples_per_pixel);
+        config.max_depth = self.max_depth.unwrap_or(config.max_depth);
+        config.lookfrom = self.lookfrom.unwrap_or(config.lookfrom);
+        config.lookat = self.lookat.unwrap_or(config.lookat);
+        config.vup = self.vup.unwrap_or(config.vup);
+        config.vfov = self.vfov.unwrap_or(config.vfov);
+        config.dist_to_focus = self.dist_to_focus.unwrap_or(config.dist_to_focus);
+        config.aperature = self.aperature.unwrap_or(config.aperature);
+        config.focus = self.focus.clone().unwrap_or(config.focus);
+        config.projection = self.projection.unwrap_or(config.projection);
+        config.bokeh = self.bokeh.clone().unwrap_or(config.bokeh);
+        config.tilt_shift = self.tilt_shift.unwrap_or(config.tilt_shift);
+        config.lens = self.lens.clone().or(config.lens);
+        config.stereo = self.stereo.or(config.stereo);
+        config.physical = self.physical.or(config.physical);
+        config.num_threads = self.num_threads.unwrap_or(config.num_threads);
+        config.tone_mapping = self.tone_mapping.unwrap_or(config.tone_mapping);
+        config.filter = self.filter.unwrap_or(config.filter);
+        config.aov_output = self.aov_output.unwrap_or(config.aov_output);
+        config.denoise = self.denoise.or(config.denoise);
+        config.seed = self.seed.or(config.seed);
+        config.region = self.region.clone().or(config.region);
+        config
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum MaterialDescription {
+    Lambertian {
+        albedo: Color,
+    },
+    Metal {
+        albedo: Color,
+        #[serde(default)]
+        fuzz: f64,
+    },
+    Dielectric {
+        ior: f64,
+    },
+    DiffuseLight {
+        emit: Color,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ObjectDescription {
+    Sphere {
+        center: Point3,
+        radius: f64,
+        material: String,
+    },
+    /// PLY or STL file, relative paths are resolved against the scene file.
+    Mesh {
+        file: PathBuf,
+        /// Multiplies the file's coordinates.
+        #[serde(default = "unit_scale")]
+        scale: f64,
+        /// Where to move the center of the mesh's bounding box, the file's
+        /// coordinates are kept when absent.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        position: Option<Point3>,
+        material: String,
+    },
+}
+
+fn unit_scale() -> f64 {
+    1.0
+}
+
+/// Transform of a `Group` relative to its parent.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GroupDescription {
+    #[serde(default = "Point3::empty")]
+    pub position: Point3,
+    /// Degrees around x, then y, then z.
+    #[serde(default = "Vec3::empty")]
+    pub rotation: Vec3,
+    #[serde(default = "unit_scale_vec")]
+    pub scale: Vec3,
+}
+
+fn unit_scale_vec() -> Vec3 {
+    Vec3::new(1.0, 1.0, 1.0)
+}
+
+impl GroupDescription {
+    pub fn from_group(group: &Group) -> GroupDescription {
+        GroupDescription {
+            position: group.position,
+            rotation: group.rotation,
+            scale: group.scale,
+        }
+    }
+
+    pub fn to_group(&self) -> Group {
+        Group {
+            position: self.position,
+            rotation: self.rotation,
+            scale: self.scale,
+        }
+    }
+}
+
+impl ObjectDescription {
+    pub fn material(&self) -> &str {
+        match self {
+            ObjectDescription::Sphere { material, .. }
+            | ObjectDescription::Mesh { material, .. } => material,
+        }
+    }
+}
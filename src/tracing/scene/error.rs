@@ -0,0 +1,97 @@
+use std::{fmt::Display, io, path::PathBuf};
+
+/// Position inside a scene file, 1 based.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SourceLocation {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl SourceLocation {
+    /// Converts a byte offset into `source` to a line and column.
+    pub fn from_offset(source: &str, offset: usize) -> SourceLocation {
+        let before = &source[..offset.min(source.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |ix| ix + 1) + 1;
+        SourceLocation { line, column }
+    }
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    UnsupportedFormat {
+        path: PathBuf,
+    },
+    Parse {
+        path: PathBuf,
+        location: Option<SourceLocation>,
+        message: String,
+    },
+    UnknownMaterial {
+        path: PathBuf,
+        location: Option<SourceLocation>,
+        object: String,
+        material: String,
+    },
+    Invalid {
+        path: PathBuf,
+        location: Option<SourceLocation>,
+        message: String,
+    },
+}
+
+impl SceneError {
+    fn path(&self) -> &PathBuf {
+        match self {
+            SceneError::Io { path, .. }
+            | SceneError::UnsupportedFormat { path }
+            | SceneError::Parse { path, .. }
+            | SceneError::UnknownMaterial { path, .. }
+            | SceneError::Invalid { path, .. } => path,
+        }
+    }
+    fn location(&self) -> Option<SourceLocation> {
+        match self {
+            SceneError::Parse { location, .. }
+            | SceneError::UnknownMaterial { location, .. }
+            | SceneError::Invalid { location, .. } => *location,
+            _ => None,
+        }
+    }
+}
+
+impl Display for SceneError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.path().display())?;
+        if let Some(location) = self.location() {
+            write!(f, ":{}:{}", location.line, location.column)?;
+        }
+        match self {
+            SceneError::Io { source, .. } => write!(f, ": {}", source),
+            SceneError::UnsupportedFormat { .. } => write!(f, ": unsupported scene file format"),
+            SceneError::Parse { message, .. } | SceneError::Invalid { message, .. } => {
+                write!(f, ": {}", message)
+            }
+            SceneError::UnknownMaterial {
+                object, material, ..
+            } => write!(
+                f,
+                ": object `{}` references unknown material `{}`",
+                object, material
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SceneError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
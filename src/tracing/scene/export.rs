@@ -0,0 +1,109 @@
+use std::{collections::BTreeMap, fs, path::Path, sync::Arc};
+
+use crate::{
+    ConfigDescription, GroupDescription, HittableMap, MaterialDescription, Scatter, SceneConfig,
+    SceneDescription, SceneError, SceneFormat, Visibility,
+};
+
+/// Builds the scene file representation of an in memory world.
+///
+/// Materials shared between objects, either the same `Arc` or identical
+/// parameters, are written once and named after the first object (in name
+/// order) that uses them.
+pub fn describe_scene(
+    world: &HittableMap,
+    config: &SceneConfig,
+) -> Result<SceneDescription, String> {
+    let mut materials: BTreeMap<String, MaterialDescription> = BTreeMap::new();
+    let mut by_pointer: Vec<(*const (), String)> = Vec::new();
+    let mut objects = BTreeMap::new();
+    let mut visibility = BTreeMap::new();
+    for (name, object) in world.iter() {
+        let mut unsupported_material = false;
+        let mut material_name = |material: &Arc<dyn Scatter>| {
+            let pointer = Arc::as_ptr(material) as *const ();
+            if let Some((_, existing)) = by_pointer.iter().find(|(p, _)| *p == pointer) {
+                return Some(existing.clone());
+            }
+            let Some(description) = material.describe() else {
+                unsupported_material = true;
+                return None;
+            };
+            let material_name = match materials.iter().find(|(_, m)| **m == description) {
+                Some((existing, _)) => existing.clone(),
+                None => {
+                    materials.insert(name.clone(), description);
+                    name.clone()
+                }
+            };
+            by_pointer.push((pointer, material_name.clone()));
+            Some(material_name)
+        };
+        let object = object.describe(&mut material_name);
+        match object {
+            Some(object) => {
+                objects.insert(name.clone(), object);
+            }
+            None if unsupported_material => {
+                return Err(format!("material of object `{}` can't be exported", name))
+            }
+            None => return Err(format!("object `{}` can't be exported", name)),
+        }
+        match world.visibility(name) {
+            Some(flags) if flags != Visibility::default() => {
+                visibility.insert(name.clone(), flags);
+            }
+            _ => {}
+        }
+    }
+
+    let groups = world
+        .groups()
+        .map(|(path, group)| (path.clone(), GroupDescription::from_group(group)))
+        .collect();
+
+    Ok(SceneDescription {
+        config: ConfigDescription::from_config(config),
+        background: Some(world.background),
+        materials,
+        groups,
+        objects,
+        visibility,
+    })
+}
+
+pub fn scene_to_string(
+    description: &SceneDescription,
+    format: SceneFormat,
+) -> Result<String, String> {
+    match format {
+        SceneFormat::Toml => toml::to_string(description).map_err(|err| err.to_string()),
+        SceneFormat::Json => {
+            serde_json::to_string_pretty(description).map_err(|err| err.to_string())
+        }
+    }
+}
+
+/// Writes `world` and `config` as a scene file that `load_scene` reads back,
+/// the format is picked from the extension.
+pub fn save_scene<P: AsRef<Path>>(
+    path: P,
+    world: &HittableMap,
+    config: &SceneConfig,
+) -> Result<(), SceneError> {
+    let path = path.as_ref();
+    let format = SceneFormat::from_path(path).ok_or_else(|| SceneError::UnsupportedFormat {
+        path: path.to_path_buf(),
+    })?;
+    let invalid = |message| SceneError::Invalid {
+        path: path.to_path_buf(),
+        location: None,
+        message,
+    };
+    let description = describe_scene(world, config).map_err(invalid)?;
+    let source = scene_to_string(&description, format).map_err(invalid)?;
+    fs::write(path, source).map_err(|source| SceneError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
@@ -0,0 +1,519 @@
+//! Loader for glTF 2.0 assets, `.gltf` with external or embedded buffers and
+//! binary `.glb`.
+//!
+//! Every node with a mesh becomes a `TriangleMesh` named after the node, its
+//! world transform baked into the vertices. Materials map onto
+//! `MetallicRoughness`, the first camera in the scene sets up the
+//! `SceneConfig` and punctual lights become small emissive spheres.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use base64::Engine;
+use gltf::{camera::Projection, khr_lights_punctual::Kind, mesh::Mode, Document, Gltf, Node};
+
+use crate::{
+    materials::{DiffuseLight, MetallicRoughness},
+    Aabb, Color, HittableMap, ImageTexture, MeshData, Point3, Scatter, Scene, SceneConfig,
+    SceneError, Sphere, Transform, TriangleMesh, Vec3,
+};
+
+/// Radius of the spheres standing in for point and spot lights.
+const LIGHT_RADIUS: f64 = 0.05;
+
+pub struct GltfImport {
+    pub scene: Scene,
+    /// Parts of the asset that were skipped or approximated.
+    pub warnings: Vec<String>,
+}
+
+/// Loads a `.gltf` or `.glb` file. External buffers and images are resolved
+/// relative to the file.
+pub fn load_gltf<P: AsRef<Path>>(path: P) -> Result<GltfImport, SceneError> {
+    let path = path.as_ref();
+    let bytes = fs::read(path).map_err(|source| SceneError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let Gltf { document, blob } = Gltf::from_slice(&bytes).map_err(|err| SceneError::Parse {
+        path: path.to_path_buf(),
+        location: None,
+        message: err.to_string(),
+    })?;
+    let mut importer = Importer {
+        path: path.to_path_buf(),
+        document: &document,
+        buffers: Vec::new(),
+        materials: HashMap::new(),
+        textures: HashMap::new(),
+        world: HittableMap::new(),
+        names: HashSet::new(),
+        camera: None,
+        bounds: Aabb::empty(),
+        warnings: Vec::new(),
+    };
+    importer.buffers = document
+        .buffers()
+        .map(|buffer| importer.buffer_data(buffer, blob.as_deref()))
+        .collect::<Result<_, _>>()?;
+    importer.import()
+}
+
+struct Importer<'a> {
+    path: PathBuf,
+    document: &'a Document,
+    buffers: Vec<Vec<u8>>,
+    materials: HashMap<Option<usize>, Arc<dyn Scatter>>,
+    textures: HashMap<usize, Option<Arc<ImageTexture>>>,
+    world: HittableMap,
+    names: HashSet<String>,
+    camera: Option<SceneConfig>,
+    bounds: Aabb,
+    warnings: Vec<String>,
+}
+
+impl<'a> Importer<'a> {
+    fn invalid(&self, message: String) -> SceneError {
+        SceneError::Invalid {
+            path: self.path.clone(),
+            location: None,
+            message,
+        }
+    }
+
+    fn read_uri(&self, uri: &str) -> Result<Vec<u8>, SceneError> {
+        if let Some(data) = uri.strip_prefix("data:") {
+            let (_, encoded) = data
+                .split_once(";base64,")
+                .ok_or_else(|| self.invalid("only base64 data URIs are supported".to_string()))?;
+            return base64::engine::general_purpose::STANDARD
+                .decode(encoded)
+                .map_err(|err| self.invalid(format!("invalid data URI: {}", err)));
+        }
+        let file = self
+            .path
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(percent_decode(uri));
+        fs::read(&file).map_err(|source| SceneError::Io { path: file, source })
+    }
+
+    fn buffer_data(
+        &self,
+        buffer: gltf::Buffer,
+        blob: Option<&[u8]>,
+    ) -> Result<Vec<u8>, SceneError> {
+        let data = match buffer.source() {
+            gltf::buffer::Source::Bin => blob
+                .map(<[u8]>::to_vec)
+                .ok_or_else(|| self.invalid("missing binary chunk".to_string()))?,
+            gltf::buffer::Source::Uri(uri) => self.read_uri(uri)?,
+        };
+        if data.len() < buffer.length() {
+            return Err(self.invalid(format!(
+                "buffer {} has {} bytes, expected {}",
+                buffer.index(),
+                data.len(),
+                buffer.length()
+            )));
+        }
+        Ok(data)
+    }
+
+    fn import(mut self) -> Result<GltfImport, SceneError> {
+        let scene = self
+            .document
+            .default_scene()
+            .or_else(|| self.document.scenes().next())
+            .ok_or_else(|| self.invalid("the asset has no scene".to_string()))?;
+        for node in scene.nodes() {
+            self.node(node, Transform::identity())?;
+        }
+
+        let config = match self.camera {
+            Some(config) => config,
+            None => self.framing_camera(),
+        };
+        Ok(GltfImport {
+            scene: Scene {
+                world: self.world,
+                config,
+            },
+            warnings: self.warnings,
+        })
+    }
+
+    // glTF names are optional and not unique
+    fn unique_name(&mut self, name: String) -> String {
+        let mut unique = name.clone();
+        let mut n = 2;
+        while self.names.contains(&unique) {
+            unique = format!("{}_{}", name, n);
+            n += 1;
+        }
+        self.names.insert(unique.clone());
+        unique
+    }
+
+    fn node(&mut self, node: Node, parent: Transform) -> Result<(), SceneError> {
+        let local = node.transform().matrix();
+        // glTF matrices are column major
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = local[j][i] as f64;
+            }
+        }
+        let to_world = match Transform::from_matrix(m) {
+            Some(local) => parent * local,
+            None => {
+                self.warnings.push(format!(
+                    "node {} has a singular transform, skipped",
+                    node.index()
+                ));
+                return Ok(());
+            }
+        };
+        let name = node
+            .name()
+            .map_or_else(|| format!("node_{}", node.index()), str::to_string);
+
+        if let Some(mesh) = node.mesh() {
+            if node.skin().is_some() || node.weights().is_some() {
+                self.warnings.push(format!(
+                    "skinning and morph targets of `{}` are ignored",
+                    name
+                ));
+            }
+            self.mesh(&name, mesh, &to_world)?;
+        }
+        if let Some(camera) = node.camera() {
+            if self.camera.is_none() {
+                self.camera = Some(camera_config(camera, &to_world));
+            }
+        }
+        if let Some(light) = node.light() {
+            let light_name = if node.mesh().is_some() {
+                format!("{}_light", name)
+            } else {
+                name.clone()
+            };
+            self.light(light_name, light, &to_world);
+        }
+        for child in node.children() {
+            self.node(child, to_world)?;
+        }
+        Ok(())
+    }
+
+    fn mesh(
+        &mut self,
+        name: &str,
+        mesh: gltf::Mesh,
+        to_world: &Transform,
+    ) -> Result<(), SceneError> {
+        let mut data = MeshData {
+            uvs: Some(Vec::new()),
+            ..MeshData::default()
+        };
+        let mut normals = Vec::new();
+        let mut has_normals = true;
+        let mut colors = Vec::new();
+        let mut has_colors = false;
+        let mut materials: Vec<Arc<dyn Scatter>> = Vec::new();
+        let mut triangle_materials = Vec::new();
+
+        for primitive in mesh.primitives() {
+            if primitive.mode() != Mode::Triangles {
+                self.warnings.push(format!(
+                    "`{}` has a {:?} primitive, only triangles are supported",
+                    name,
+                    primitive.mode()
+                ));
+                continue;
+            }
+            let buffers = &self.buffers;
+            let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(Vec::as_slice));
+            let Some(positions) = reader.read_positions() else {
+                continue;
+            };
+            let offset = data.positions.len();
+            data.positions.extend(
+                positions
+                    .map(|[x, y, z]| to_world.point(Point3::new(x as f64, y as f64, z as f64))),
+            );
+            let vertex_count = data.positions.len() - offset;
+
+            match reader.read_normals() {
+                Some(primitive_normals) => normals.extend(primitive_normals.map(|[x, y, z]| {
+                    to_world
+                        .normal(Vec3::new(x as f64, y as f64, z as f64))
+                        .unit_vector()
+                })),
+                None => has_normals = false,
+            }
+            match reader.read_colors(0) {
+                Some(primitive_colors) => {
+                    has_colors = true;
+                    colors.extend(
+                        primitive_colors
+                            .into_rgb_f32()
+                            .map(|[r, g, b]| Color::new(r as f64, g as f64, b as f64)),
+                    );
+                }
+                None => colors.extend(std::iter::repeat_n(Color::new(1.0, 1.0, 1.0), vertex_count)),
+            }
+
+            let material = primitive.material();
+            let tex_coord = material
+                .pbr_metallic_roughness()
+                .base_color_texture()
+                .map_or(0, |info| info.tex_coord());
+            let uvs = data.uvs.as_mut().unwrap();
+            match reader.read_tex_coords(tex_coord) {
+                // glTF's v points down the image
+                Some(tex_coords) => uvs.extend(
+                    tex_coords
+                        .into_f32()
+                        .map(|[u, v]| (u as f64, 1.0 - v as f64)),
+                ),
+                None => uvs.extend(std::iter::repeat_n((0.0, 0.0), vertex_count)),
+            }
+
+            let indices: Vec<usize> = match reader.read_indices() {
+                Some(indices) => indices.into_u32().map(|ix| ix as usize + offset).collect(),
+                None => (offset..offset + vertex_count).collect(),
+            };
+            if indices.iter().any(|ix| *ix >= offset + vertex_count) {
+                return Err(self.invalid(format!("`{}` has an out of range vertex index", name)));
+            }
+            let first_triangle = data.indices.len();
+            data.indices.extend(
+                indices
+                    .chunks_exact(3)
+                    .map(|triangle| [triangle[0], triangle[1], triangle[2]]),
+            );
+
+            let scatter = self.material(material)?;
+            let material_ix = match materials.iter().position(|m| Arc::ptr_eq(m, &scatter)) {
+                Some(ix) => ix,
+                None => {
+                    materials.push(scatter);
+                    materials.len() - 1
+                }
+            };
+            triangle_materials.extend(std::iter::repeat_n(
+                material_ix,
+                data.indices.len() - first_triangle,
+            ));
+        }
+
+        if data.indices.is_empty() {
+            return Ok(());
+        }
+        if has_normals {
+            data.normals = Some(normals);
+        }
+        if has_colors {
+            data.colors = Some(colors);
+        }
+        if to_world.swaps_handedness() {
+            for triangle in &mut data.indices {
+                triangle.swap(1, 2);
+            }
+        }
+        self.bounds = data
+            .positions
+            .iter()
+            .fold(self.bounds, |bounds, p| bounds.grow(*p));
+
+        let name = self.unique_name(name.to_string());
+        let mesh = if materials.len() == 1 {
+            TriangleMesh::new(data, materials.remove(0))
+        } else {
+            TriangleMesh::with_materials(data, materials, triangle_materials)
+        };
+        self.world.add(name, Box::new(mesh));
+        Ok(())
+    }
+
+    fn material(&mut self, material: gltf::Material) -> Result<Arc<dyn Scatter>, SceneError> {
+        if let Some(scatter) = self.materials.get(&material.index()) {
+            return Ok(Arc::clone(scatter));
+        }
+        let pbr = material.pbr_metallic_roughness();
+        let [r, g, b, _] = pbr.base_color_factor();
+        let base_color_texture = match pbr.base_color_texture() {
+            Some(info) => self.texture(info.texture())?,
+            None => None,
+        };
+        let [er, eg, eb] = material.emissive_factor();
+        let name = material.name().unwrap_or("default");
+        if pbr.metallic_roughness_texture().is_some() || material.normal_texture().is_some() {
+            self.warnings.push(format!(
+                "only the base color texture of material `{}` is used",
+                name
+            ));
+        }
+        if material.emissive_texture().is_some() {
+            self.warnings.push(format!(
+                "emissive texture of material `{}` is ignored",
+                name
+            ));
+        }
+        let scatter: Arc<dyn Scatter> = Arc::new(MetallicRoughness::new(
+            Color::new(r as f64, g as f64, b as f64),
+            base_color_texture,
+            pbr.metallic_factor() as f64,
+            pbr.roughness_factor() as f64,
+            Color::new(er as f64, eg as f64, eb as f64),
+        ));
+        self.materials
+            .insert(material.index(), Arc::clone(&scatter));
+        Ok(scatter)
+    }
+
+    fn texture(&mut self, texture: gltf::Texture) -> Result<Option<Arc<ImageTexture>>, SceneError> {
+        let image = texture.source();
+        if let Some(texture) = self.textures.get(&image.index()) {
+            return Ok(texture.clone());
+        }
+        let bytes = match image.source() {
+            gltf::image::Source::View { view, .. } => {
+                let buffer = &self.buffers[view.buffer().index()];
+                buffer
+                    .get(view.offset()..view.offset() + view.length())
+                    .ok_or_else(|| {
+                        self.invalid(format!("image {} is out of bounds", image.index()))
+                    })?
+                    .to_vec()
+            }
+            gltf::image::Source::Uri { uri, .. } => self.read_uri(uri)?,
+        };
+        let decoded = match image::load_from_memory(&bytes) {
+            Ok(decoded) => Some(Arc::new(ImageTexture::from_image(&decoded, true))),
+            Err(err) => {
+                self.warnings.push(format!(
+                    "image {} could not be decoded, ignored: {}",
+                    image.index(),
+                    err
+                ));
+                None
+            }
+        };
+        self.textures.insert(image.index(), decoded.clone());
+        Ok(decoded)
+    }
+
+    fn light(
+        &mut self,
+        name: String,
+        light: gltf::khr_lights_punctual::Light,
+        to_world: &Transform,
+    ) {
+        match light.kind() {
+            Kind::Directional => {
+                self.warnings.push(format!(
+                    "directional light `{}` is not supported, skipped",
+                    name
+                ));
+                return;
+            }
+            Kind::Spot { .. } => self.warnings.push(format!(
+                "spot light `{}` is approximated by a point light",
+                name
+            )),
+            Kind::Point => {}
+        }
+        // a sphere with radiance L has an intensity of L * pi * r^2
+        let [r, g, b] = light.color();
+        let radiance = light.intensity() as f64
+            / (std::f64::consts::PI * LIGHT_RADIUS * LIGHT_RADIUS)
+            * Color::new(r as f64, g as f64, b as f64);
+        let name = self.unique_name(name);
+        self.world.add(
+            name,
+            Box::new(Sphere::new(
+                to_world.point(Point3::empty()),
+                LIGHT_RADIUS,
+                Arc::new(DiffuseLight::new(radiance)),
+            )),
+        );
+    }
+
+    // Without a camera in the asset, look at the meshes from +z
+    fn framing_camera(&self) -> SceneConfig {
+        let mut config = SceneConfig::default();
+        if self.bounds.min.x() > self.bounds.max.x() {
+            return config;
+        }
+        let center = self.bounds.center();
+        let radius = (self.bounds.max - center).length().max(1.0e-3);
+        config.vfov = 40.0;
+        config.lookat = center;
+        config.lookfrom = center
+            + Vec3::new(
+                0.0,
+                0.0,
+                1.1 * radius / (config.vfov / 2.0).to_radians().tan(),
+            );
+        config.vup = Vec3::new(0.0, 1.0, 0.0);
+        config.aperature = 0.0;
+        config.dist_to_focus = (config.lookat - config.lookfrom).length();
+        config
+    }
+}
+
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut ix = 0;
+    while ix < bytes.len() {
+        let escaped = (bytes[ix] == b'%')
+            .then(|| uri.get(ix + 1..ix + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                ix += 3;
+            }
+            None => {
+                decoded.push(bytes[ix]);
+                ix += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn camera_config(camera: gltf::Camera, to_world: &Transform) -> SceneConfig {
+    let mut config = SceneConfig::default();
+    match camera.projection() {
+        Projection::Perspective(perspective) => {
+            if let Some(aspect_ratio) = perspective.aspect_ratio() {
+                config.aspect_ratio = aspect_ratio as f64;
+            }
+            config.vfov = (perspective.yfov() as f64).to_degrees();
+        }
+        Projection::Orthographic(orthographic) => {
+            // xmag and ymag are half the width and height of the view
+            config.aspect_ratio = (orthographic.xmag() / orthographic.ymag()) as f64;
+            config.projection = crate::Projection::Orthographic {
+                view_height: 2.0 * orthographic.ymag() as f64,
+            };
+        }
+    }
+    config.image_height = (config.image_width as f64 / config.aspect_ratio) as u32;
+    // glTF cameras look down -z with +y up
+    config.lookfrom = to_world.point(Point3::empty());
+    config.lookat = to_world.point(Point3::new(0.0, 0.0, -1.0));
+    config.vup = to_world.vector(Vec3::new(0.0, 1.0, 0.0));
+    config.aperature = 0.0;
+    config.dist_to_focus = (config.lookat - config.lookfrom).length();
+    config
+}
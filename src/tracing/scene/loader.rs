@@ -0,0 +1,460 @@
+use std::{collections::HashMap, fs, path::Path, sync::Arc};
+
+use serde::de::DeserializeOwned;
+
+use crate::{
+    materials::{Dielectric, DiffuseLight, Lambertian, Metal},
+    Anchored, ApertureShape, CameraAnimation, Focus, HittableMap, LensElement, MaterialDescription,
+    ObjectDescription, RealisticCamera, Scatter, SceneConfig, SceneDescription, SceneError,
+    SourceLocation, Sphere, TriangleMesh,
+};
+
+/// A loaded scene, ready to hand to `render_scene`.
+pub struct Scene {
+    pub world: HittableMap,
+    pub config: SceneConfig,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SceneFormat {
+    Toml,
+    Json,
+}
+
+impl SceneFormat {
+    pub fn from_path(path: &Path) -> Option<SceneFormat> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "toml" => Some(SceneFormat::Toml),
+            "json" => Some(SceneFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Loads a `.toml` or `.json` scene file.
+pub fn load_scene<P: AsRef<Path>>(path: P) -> Result<Scene, SceneError> {
+    let path = path.as_ref();
+    let format = SceneFormat::from_path(path).ok_or_else(|| SceneError::UnsupportedFormat {
+        path: path.to_path_buf(),
+    })?;
+    let source = fs::read_to_string(path).map_err(|source| SceneError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    parse_scene(&source, format, path)
+}
+
+/// Parses and builds a scene from `source`, `path` is only used for error messages.
+pub fn parse_scene(source: &str, format: SceneFormat, path: &Path) -> Result<Scene, SceneError> {
+    let description = parse_description(source, format, path)?;
+    build_scene(&description, source, path)
+}
+
+pub fn parse_description(
+    source: &str,
+    format: SceneFormat,
+    path: &Path,
+) -> Result<SceneDescription, SceneError> {
+    parse_source(source, format, path)
+}
+
+/// Loads a `.toml` or `.json` file of camera keyframes for `animate`.
+pub fn load_camera_animation<P: AsRef<Path>>(path: P) -> Result<CameraAnimation, SceneError> {
+    let path = path.as_ref();
+    let format = SceneFormat::from_path(path).ok_or_else(|| SceneError::UnsupportedFormat {
+        path: path.to_path_buf(),
+    })?;
+    let source = fs::read_to_string(path).map_err(|source| SceneError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    parse_source(&source, format, path)
+}
+
+fn parse_source<T: DeserializeOwned>(
+    source: &str,
+    format: SceneFormat,
+    path: &Path,
+) -> Result<T, SceneError> {
+    match format {
+        SceneFormat::Toml => toml::from_str(source).map_err(|err| SceneError::Parse {
+            path: path.to_path_buf(),
+            location: err
+                .span()
+                .map(|span| SourceLocation::from_offset(source, span.start)),
+            message: err.message().to_string(),
+        }),
+        SceneFormat::Json => serde_json::from_str(source).map_err(|err| SceneError::Parse {
+            path: path.to_path_buf(),
+            location: Some(SourceLocation {
+                line: err.line(),
+                column: err.column(),
+            }),
+            // the location is reported separately
+            message: err
+                .to_string()
+                .trim_end_matches(&format!(" at line {} column {}", err.line(), err.column()))
+                .to_string(),
+        }),
+    }
+}
+
+fn build_scene(
+    description: &SceneDescription,
+    source: &str,
+    path: &Path,
+) -> Result<Scene, SceneError> {
+    let mut config = description.config.to_config();
+    if config.image_width < 2 || config.image_height < 2 {
+        // a derived height comes from the aspect ratio
+        let key = if config.image_width < 2 {
+            "image_width"
+        } else {
+            "image_height"
+        };
+        return Err(SceneError::Invalid {
+            path: path.to_path_buf(),
+            location: find_key(source, key).or_else(|| find_key(source, "aspect_ratio")),
+            message: format!(
+                "image must be at least 2x2 pixels, got {}x{}",
+                config.image_width, config.image_height
+            ),
+        });
+    }
+    if config.samples_per_pixel < 1 {
+        return Err(SceneError::Invalid {
+            path: path.to_path_buf(),
+            location: find_key(source, "samples_per_pixel"),
+            message: "samples_per_pixel must be at least 1".to_string(),
+        });
+    }
+    if config.num_threads < 1 {
+        return Err(SceneError::Invalid {
+            path: path.to_path_buf(),
+            location: find_key(source, "num_threads"),
+            message: "num_threads must be at least 1".to_string(),
+        });
+    }
+    if !config.tone_mapping.is_valid() {
+        return Err(SceneError::Invalid {
+            path: path.to_path_buf(),
+            location: find_key(source, "tone_mapping"),
+            message: "tone mapping needs a positive white_point and gamma".to_string(),
+        });
+    }
+    let size = &description.config;
+    if let (Some(aspect_ratio), Some(width), Some(height)) =
+        (size.aspect_ratio, size.image_width, size.image_height)
+    {
+        // heights derived from the aspect ratio are rounded down
+        if (width as f64 / aspect_ratio - height as f64).abs() >= 1.0 {
+            return Err(SceneError::Invalid {
+                path: path.to_path_buf(),
+                location: find_key(source, "aspect_ratio"),
+                message: format!(
+                    "aspect_ratio {} doesn't match the {}x{} image, leave one of them out",
+                    aspect_ratio, width, height
+                ),
+            });
+        }
+    }
+    if let ApertureShape::Mask { file, .. } = &config.bokeh.shape {
+        let invalid = |message| SceneError::Invalid {
+            path: path.to_path_buf(),
+            location: find_reference(source, "file", &file.to_string_lossy()),
+            message,
+        };
+        let mask_path = path.parent().unwrap_or(Path::new("")).join(file);
+        let shape = ApertureShape::mask(&mask_path)
+            .map_err(|err| invalid(format!("aperture mask: {}", err)))?;
+        if let ApertureShape::Mask {
+            mask: Some(mask), ..
+        } = &shape
+        {
+            if !mask.is_open() {
+                return Err(invalid("aperture mask is completely black".to_string()));
+            }
+        }
+        config.bokeh.shape = shape;
+    }
+    if let Some(region) = &mut config.region {
+        if !region.fits(config.image_width, config.image_height) {
+            return Err(SceneError::Invalid {
+                path: path.to_path_buf(),
+                location: find_key(source, "region"),
+                message: format!(
+                    "region {}x{} at {},{} isn't inside the {}x{} image",
+                    region.width,
+                    region.height,
+                    region.x,
+                    region.y,
+                    config.image_width,
+                    config.image_height
+                ),
+            });
+        }
+        if let Some(background) = &region.background {
+            let background = path.parent().unwrap_or(Path::new("")).join(background);
+            region.background = Some(std::fs::canonicalize(&background).unwrap_or(background));
+        }
+    }
+    if let Some(stereo) = config.stereo {
+        if stereo.interocular < 0.0 || stereo.convergence <= 0.0 {
+            return Err(SceneError::Invalid {
+                path: path.to_path_buf(),
+                location: find_key(source, "stereo"),
+                message: "stereo needs a non-negative interocular and a positive convergence"
+                    .to_string(),
+            });
+        }
+    }
+    if let Some(lens) = &mut config.lens {
+        if let Some(file) = lens.file.take() {
+            let invalid = |message| SceneError::Invalid {
+                path: path.to_path_buf(),
+                location: find_reference(source, "file", &file.to_string_lossy()),
+                message,
+            };
+            let table_path = path.parent().unwrap_or(Path::new("")).join(&file);
+            let table = std::fs::read_to_string(&table_path)
+                .map_err(|err| invalid(format!("lens file: {}", err)))?;
+            lens.elements = LensElement::parse_table(&table)
+                .map_err(|err| invalid(format!("lens file {}", err)))?;
+        }
+    }
+    if let Some(lens) = &config.lens {
+        if !RealisticCamera::can_focus(&config, lens) {
+            return Err(SceneError::Invalid {
+                path: path.to_path_buf(),
+                location: find_key(source, "lens"),
+                message: format!(
+                    "the lens doesn't let light through focused at {}",
+                    config.dist_to_focus
+                ),
+            });
+        }
+    }
+    if config.physical.is_some_and(|physical| !physical.is_valid()) {
+        return Err(SceneError::Invalid {
+            path: path.to_path_buf(),
+            location: find_key(source, "physical"),
+            message: "physical camera settings must all be positive".to_string(),
+        });
+    }
+
+    let materials: HashMap<&str, Arc<dyn Scatter>> = description
+        .materials
+        .iter()
+        .map(|(name, material)| (name.as_str(), build_material(material)))
+        .collect();
+
+    let mut world = HittableMap::new();
+    if let Some(background) = description.background {
+        world.background = background;
+    }
+    for (path, group) in &description.groups {
+        world.add_group(path.clone(), group.to_group());
+    }
+    for (name, object) in &description.objects {
+        let material =
+            materials
+                .get(object.material())
+                .ok_or_else(|| SceneError::UnknownMaterial {
+                    path: path.to_path_buf(),
+                    location: find_reference(source, "material", object.material()),
+                    object: name.clone(),
+                    material: object.material().to_string(),
+                })?;
+        match object {
+            ObjectDescription::Sphere { center, radius, .. } => {
+                world.add(
+                    name.clone(),
+                    Box::new(Sphere::new(*center, *radius, Arc::clone(material))),
+                );
+            }
+            ObjectDescription::Mesh {
+                file,
+                scale,
+                position,
+                ..
+            } => {
+                let mesh_path = path.parent().unwrap_or(Path::new("")).join(file);
+                let mut mesh = TriangleMesh::load(&mesh_path, *scale, Arc::clone(material))
+                    .map_err(|err| SceneError::Invalid {
+                        path: path.to_path_buf(),
+                        location: find_reference(source, "file", &file.to_string_lossy()),
+                        message: format!("object `{}`: {}", name, err),
+                    })?;
+                if let Some(position) = position {
+                    mesh.set_origin(*position);
+                }
+                world.add(name.clone(), Box::new(mesh));
+            }
+        }
+    }
+    for (name, visibility) in &description.visibility {
+        if !world.set_visibility(name, *visibility) {
+            return Err(SceneError::Invalid {
+                path: path.to_path_buf(),
+                location: find_key(source, name),
+                message: format!("visibility given for unknown object `{}`", name),
+            });
+        }
+    }
+    match &config.focus {
+        Focus::Object { name } if world.origin(name).is_none() => {
+            return Err(SceneError::Invalid {
+                path: path.to_path_buf(),
+                location: find_reference(source, "name", name),
+                message: format!("focus on unknown object `{}`", name),
+            });
+        }
+        Focus::Pixel { x, y } if *x >= config.image_width || *y >= config.image_height => {
+            return Err(SceneError::Invalid {
+                path: path.to_path_buf(),
+                location: find_key(source, "focus"),
+                message: format!(
+                    "focus pixel {},{} is outside the {}x{} image",
+                    x, y, config.image_width, config.image_height
+                ),
+            });
+        }
+        _ => {}
+    }
+
+    Ok(Scene { world, config })
+}
+
+pub fn build_material(material: &MaterialDescription) -> Arc<dyn Scatter> {
+    match *material {
+        MaterialDescription::Lambertian { albedo } => Arc::new(Lambertian::new(albedo)),
+        MaterialDescription::Metal { albedo, fuzz } => Arc::new(Metal::new(albedo, fuzz)),
+        MaterialDescription::Dielectric { ior } => Arc::new(Dielectric::new(ior)),
+        MaterialDescription::DiffuseLight { emit } => Arc::new(DiffuseLight::new(emit)),
+    }
+}
+
+// The deserialized description has no positions, so references are located
+// by looking for the key and quoted value on the same line. Works for both
+// TOML and JSON as long as the pair is not split across lines.
+fn find_reference(source: &str, key: &str, value: &str) -> Option<SourceLocation> {
+    let quoted = format!("\"{}\"", value);
+    let mut offset = 0;
+    for line in source.split_inclusive('\n') {
+        if line.contains(key) {
+            if let Some(ix) = line.find(&quoted) {
+                return Some(SourceLocation::from_offset(source, offset + ix));
+            }
+        }
+        offset += line.len();
+    }
+    None
+}
+
+// Keys are located where they're defined, `key = ` or a `[table.key]` header
+// at the start of a TOML line, or `"key":` in JSON, so the same word in a
+// comment, a value or another name doesn't count.
+fn find_key(source: &str, key: &str) -> Option<SourceLocation> {
+    let quoted = format!("\"{}\"", key);
+    let mut offset = 0;
+    for line in source.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        let start = offset + line.len() - trimmed.len();
+        let assigned = [key, quoted.as_str()].iter().any(|name| {
+            trimmed
+                .strip_prefix(name)
+                .is_some_and(|rest| rest.trim_start().starts_with('='))
+        });
+        let header = trimmed
+            .strip_prefix('[')
+            .and_then(|header| header.split(']').next())
+            .is_some_and(|header| {
+                header
+                    .trim_start_matches('[')
+                    .split('.')
+                    .any(|part| part.trim() == key || part.trim() == quoted)
+            });
+        if assigned || header {
+            return Some(SourceLocation::from_offset(source, start));
+        }
+        let mut from = 0;
+        while let Some(ix) = line[from..].find(&quoted) {
+            let end = from + ix + quoted.len();
+            if line[end..].trim_start().starts_with(':') {
+                return Some(SourceLocation::from_offset(source, offset + from + ix));
+            }
+            from = end;
+        }
+        offset += line.len();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_toml_keys_where_they_are_defined() {
+        let source = "# focus on the sphere
+[objects.focus_sphere]
+type = \"sphere\"
+material = \"focus\"
+
+[config]
+  focus = { type = \"lookat\" }
+";
+        assert_eq!(
+            find_key(source, "focus"),
+            Some(SourceLocation { line: 7, column: 3 })
+        );
+        assert_eq!(
+            find_key(source, "focus_sphere"),
+            Some(SourceLocation { line: 2, column: 1 })
+        );
+        assert_eq!(find_key(source, "sphere"), None);
+    }
+
+    #[test]
+    fn finds_toml_table_headers_and_quoted_keys() {
+        let source = "[config.region]\nx = 1\n\n[visibility.\"car/body\"]\ncamera = false\n";
+        assert_eq!(
+            find_key(source, "region"),
+            Some(SourceLocation { line: 1, column: 1 })
+        );
+        assert_eq!(
+            find_key(source, "car/body"),
+            Some(SourceLocation { line: 4, column: 1 })
+        );
+    }
+
+    #[test]
+    fn finds_json_keys() {
+        let source = r#"{"objects": {"focus_sphere": {"material": "focus"}},
+ "config": {"image_width": 10, "focus": {"type": "lookat"}}}"#;
+        assert_eq!(
+            find_key(source, "focus"),
+            Some(SourceLocation {
+                line: 2,
+                column: 32
+            })
+        );
+    }
+
+    #[test]
+    fn reports_errors_at_the_offending_key() {
+        let source = "# num_threads is left at its default
+[config]
+image_width = 100
+image_height = 1
+";
+        let error = parse_scene(source, SceneFormat::Toml, Path::new("scene.toml")).err();
+        assert!(matches!(
+            error,
+            Some(SceneError::Invalid {
+                location: Some(SourceLocation { line: 4, column: 1 }),
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,12 @@
+pub mod description;
+pub use description::*;
+pub mod error;
+pub use error::*;
+pub mod loader;
+pub use loader::*;
+pub mod export;
+pub use export::*;
+pub mod pbrt;
+pub use pbrt::*;
+pub mod gltf_import;
+pub use gltf_import::*;
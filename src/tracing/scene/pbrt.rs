@@ -0,0 +1,1187 @@
+//! Importer for the subset of the pbrt-v3 and pbrt-v4 scene format that maps
+//! onto this renderer.
+//!
+//! pbrt uses a left handed coordinate system, the imported scene is mirrored
+//! along x so that renders match pbrt's images. Directives, parameters and
+//! shapes that can't be represented are skipped and reported as warnings.
+
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    load_mesh,
+    materials::{Dielectric, DiffuseLight, Lambertian, Metal},
+    Background, Color, HittableMap, MeshData, MeshError, Point3, Projection, Scatter, Scene,
+    SceneConfig, SceneError, SourceLocation, Sphere, Transform, TriangleMesh, Vec3,
+};
+
+/// A directive or parameter of a pbrt file that was skipped or approximated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PbrtWarning {
+    pub path: PathBuf,
+    pub location: SourceLocation,
+    pub message: String,
+}
+
+impl Display for PbrtWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            self.path.display(),
+            self.location.line,
+            self.location.column,
+            self.message
+        )
+    }
+}
+
+pub struct PbrtImport {
+    pub scene: Scene,
+    pub warnings: Vec<PbrtWarning>,
+}
+
+/// Imports a pbrt-v3 or pbrt-v4 scene file. `Include` and `Import` paths are
+/// resolved relative to the including file.
+pub fn load_pbrt<P: AsRef<Path>>(path: P) -> Result<PbrtImport, SceneError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path).map_err(|source| SceneError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    parse_pbrt(&source, path)
+}
+
+/// Imports pbrt scene source, `path` is used for error messages and to
+/// resolve included files and meshes.
+pub fn parse_pbrt(source: &str, path: &Path) -> Result<PbrtImport, SceneError> {
+    let mut importer = Importer::new(path);
+    importer.tokens = tokenize(source, 0).map_err(|(location, message)| SceneError::Parse {
+        path: path.to_path_buf(),
+        location: Some(location),
+        message,
+    })?;
+    importer.run()?;
+    importer.finish()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Ident(String),
+    Str(String),
+    Num(f64),
+    Open,
+    Close,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    file: usize,
+    location: SourceLocation,
+}
+
+impl Token {
+    // `true` and `false` may appear unquoted as parameter values
+    fn is_directive(&self) -> bool {
+        matches!(&self.kind, TokenKind::Ident(word) if word != "true" && word != "false")
+    }
+}
+
+fn tokenize(source: &str, file: usize) -> Result<Vec<Token>, (SourceLocation, String)> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    let location = |offset| SourceLocation::from_offset(source, offset);
+    while let Some((start, c)) = chars.next() {
+        let kind = match c {
+            _ if c.is_whitespace() => continue,
+            '#' => {
+                while chars.next_if(|(_, c)| *c != '\n').is_some() {}
+                continue;
+            }
+            '[' => TokenKind::Open,
+            ']' => TokenKind::Close,
+            '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, 'n')) => value.push('\n'),
+                            Some((_, 't')) => value.push('\t'),
+                            Some((_, c)) => value.push(c),
+                            None => break,
+                        },
+                        Some((_, '\n')) | None => {
+                            return Err((location(start), "unterminated string".to_string()))
+                        }
+                        Some((_, c)) => value.push(c),
+                    }
+                }
+                TokenKind::Str(value)
+            }
+            _ => {
+                let mut end = start + c.len_utf8();
+                while let Some((ix, c)) = chars
+                    .next_if(|(_, c)| !c.is_whitespace() && !matches!(c, '[' | ']' | '"' | '#'))
+                {
+                    end = ix + c.len_utf8();
+                }
+                let word = &source[start..end];
+                match word.parse::<f64>() {
+                    Ok(value) => TokenKind::Num(value),
+                    Err(_) => TokenKind::Ident(word.to_string()),
+                }
+            }
+        };
+        tokens.push(Token {
+            kind,
+            file,
+            location: location(start),
+        });
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ParamValue {
+    Num(f64),
+    Str(String),
+    Bool(bool),
+}
+
+#[derive(Debug)]
+struct Param {
+    ty: String,
+    name: String,
+    values: Vec<ParamValue>,
+    token: Token,
+    used: Cell<bool>,
+}
+
+/// The `"type name" value` list following a directive. Lookups mark the
+/// parameter as used, whatever is left unused is reported.
+#[derive(Debug, Default)]
+struct ParamSet {
+    params: Vec<Param>,
+}
+
+impl ParamSet {
+    fn find(&self, name: &str) -> Option<&Param> {
+        let param = self.params.iter().find(|param| param.name == name)?;
+        param.used.set(true);
+        Some(param)
+    }
+    fn floats(&self, name: &str) -> Option<Vec<f64>> {
+        let param = self.find(name)?;
+        param
+            .values
+            .iter()
+            .map(|value| match value {
+                ParamValue::Num(value) => Some(*value),
+                _ => None,
+            })
+            .collect()
+    }
+    fn float(&self, name: &str) -> Option<f64> {
+        self.floats(name)?.first().copied()
+    }
+    fn string(&self, name: &str) -> Option<String> {
+        match self.find(name)?.values.first()? {
+            ParamValue::Str(value) => Some(value.clone()),
+            _ => None,
+        }
+    }
+    fn bool(&self, name: &str) -> Option<bool> {
+        match self.find(name)?.values.first()? {
+            ParamValue::Bool(value) => Some(*value),
+            ParamValue::Str(value) => Some(value == "true"),
+            _ => None,
+        }
+    }
+    fn ty(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|param| param.name == name)
+            .map(|param| param.ty.as_str())
+    }
+    fn unused(&self) -> impl Iterator<Item = &Param> {
+        self.params.iter().filter(|param| !param.used.get())
+    }
+}
+
+#[derive(Clone)]
+struct GraphicsState {
+    ctm: Transform,
+    material: Arc<dyn Scatter>,
+    area_light: Option<Arc<dyn Scatter>>,
+    reverse_orientation: bool,
+}
+
+struct CameraState {
+    world_from_camera: Transform,
+    orthographic: bool,
+    screen_window: Option<[f64; 4]>,
+    fov: f64,
+    lens_radius: f64,
+    focal_distance: f64,
+}
+
+struct Importer {
+    files: Vec<PathBuf>,
+    tokens: Vec<Token>,
+    pos: usize,
+    warnings: Vec<PbrtWarning>,
+    graphics: GraphicsState,
+    // the flag tells `AttributeEnd` from `TransformEnd` scopes
+    stack: Vec<(GraphicsState, bool)>,
+    named_materials: HashMap<String, Arc<dyn Scatter>>,
+    named_coordinate_systems: HashMap<String, Transform>,
+    object_depth: usize,
+    camera: Option<CameraState>,
+    config: SceneConfig,
+    resolution: (u32, u32),
+    world: HittableMap,
+    shape_count: usize,
+}
+
+fn default_material() -> Arc<dyn Scatter> {
+    Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+}
+
+// Converts pbrt's left handed world to this renderer's right handed one.
+fn mirror() -> Transform {
+    Transform::scale(-1.0, 1.0, 1.0)
+}
+
+impl Importer {
+    fn new(path: &Path) -> Importer {
+        let mut world = HittableMap::new();
+        // pbrt scenes without an environment light are black
+        world.background = Background::Solid {
+            color: Color::empty(),
+        };
+        Importer {
+            files: vec![path.to_path_buf()],
+            tokens: Vec::new(),
+            pos: 0,
+            warnings: Vec::new(),
+            graphics: GraphicsState {
+                ctm: Transform::identity(),
+                material: default_material(),
+                area_light: None,
+                reverse_orientation: false,
+            },
+            stack: Vec::new(),
+            named_materials: HashMap::new(),
+            named_coordinate_systems: HashMap::new(),
+            object_depth: 0,
+            camera: None,
+            config: SceneConfig::default(),
+            // pbrt's default film
+            resolution: (1280, 720),
+            world,
+            shape_count: 0,
+        }
+    }
+
+    fn error(&self, token: &Token, message: String) -> SceneError {
+        SceneError::Parse {
+            path: self.files[token.file].clone(),
+            location: Some(token.location),
+            message,
+        }
+    }
+
+    fn warn(&mut self, token: &Token, message: String) {
+        self.warnings.push(PbrtWarning {
+            path: self.files[token.file].clone(),
+            location: token.location,
+            message,
+        });
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn expect_string(&mut self, directive: &Token) -> Result<(Token, String), SceneError> {
+        match self.next() {
+            Some(Token {
+                kind: TokenKind::Str(value),
+                file,
+                location,
+            }) => Ok((
+                Token {
+                    kind: TokenKind::Str(value.clone()),
+                    file,
+                    location,
+                },
+                value,
+            )),
+            Some(token) => Err(self.error(&token, "expected a quoted string".to_string())),
+            None => Err(self.error(directive, "expected a quoted string".to_string())),
+        }
+    }
+
+    fn expect_number(&mut self, directive: &Token) -> Result<f64, SceneError> {
+        match self.next() {
+            Some(Token {
+                kind: TokenKind::Num(value),
+                ..
+            }) => Ok(value),
+            Some(token) => Err(self.error(&token, "expected a number".to_string())),
+            None => Err(self.error(directive, "expected a number".to_string())),
+        }
+    }
+
+    // `count` numbers, optionally enclosed in brackets
+    fn expect_numbers(&mut self, directive: &Token, count: usize) -> Result<Vec<f64>, SceneError> {
+        let bracketed = matches!(self.peek(), Some(token) if token.kind == TokenKind::Open);
+        if bracketed {
+            self.pos += 1;
+        }
+        let values = (0..count)
+            .map(|_| self.expect_number(directive))
+            .collect::<Result<Vec<_>, _>>()?;
+        if bracketed {
+            match self.next() {
+                Some(token) if token.kind == TokenKind::Close => {}
+                Some(token) => return Err(self.error(&token, "expected `]`".to_string())),
+                None => return Err(self.error(directive, "expected `]`".to_string())),
+            }
+        }
+        Ok(values)
+    }
+
+    fn parse_params(&mut self) -> Result<ParamSet, SceneError> {
+        let mut params = ParamSet::default();
+        while let Some(Token {
+            kind: TokenKind::Str(declaration),
+            ..
+        }) = self.peek().cloned()
+        {
+            let token = self.next().unwrap();
+            let mut words = declaration.split_whitespace();
+            let (Some(ty), Some(name), None) = (words.next(), words.next(), words.next()) else {
+                return Err(self.error(
+                    &token,
+                    format!(
+                        "expected a `\"type name\"` parameter, got `{}`",
+                        declaration
+                    ),
+                ));
+            };
+            let mut values = Vec::new();
+            let bracketed = matches!(self.peek(), Some(next) if next.kind == TokenKind::Open);
+            if bracketed {
+                self.pos += 1;
+            }
+            loop {
+                let Some(value) = self.next() else {
+                    return Err(self.error(&token, format!("missing value for `{}`", name)));
+                };
+                values.push(match value.kind {
+                    TokenKind::Num(value) => ParamValue::Num(value),
+                    TokenKind::Str(value) => ParamValue::Str(value),
+                    TokenKind::Ident(ref word) if word == "true" => ParamValue::Bool(true),
+                    TokenKind::Ident(ref word) if word == "false" => ParamValue::Bool(false),
+                    TokenKind::Close if bracketed => break,
+                    _ => return Err(self.error(&value, format!("invalid value for `{}`", name))),
+                });
+                if !bracketed {
+                    break;
+                }
+            }
+            params.params.push(Param {
+                ty: ty.to_string(),
+                name: name.to_string(),
+                values,
+                token,
+                used: Cell::new(false),
+            });
+        }
+        Ok(params)
+    }
+
+    fn report_unused(&mut self, params: &ParamSet, context: &str) {
+        for param in params.unused() {
+            self.warnings.push(PbrtWarning {
+                path: self.files[param.token.file].clone(),
+                location: param.token.location,
+                message: format!(
+                    "unsupported parameter `{} {}` of {} ignored",
+                    param.ty, param.name, context
+                ),
+            });
+        }
+    }
+
+    // Looks up a color parameter, spectra and textures can't be represented
+    fn color(&mut self, params: &ParamSet, name: &str) -> Option<Color> {
+        let ty = params.ty(name)?.to_string();
+        let values = params.floats(name);
+        match (ty.as_str(), values.as_deref()) {
+            ("rgb" | "color", Some(&[r, g, b])) => Some(Color::new(r, g, b)),
+            ("float", Some(&[value])) => Some(Color::new(value, value, value)),
+            _ => {
+                let token = params.find(name).unwrap().token.clone();
+                self.warn(
+                    &token,
+                    format!("`{} {}` can't be represented, using the default", ty, name),
+                );
+                None
+            }
+        }
+    }
+
+    fn skip_arguments(&mut self) {
+        while matches!(self.peek(), Some(token) if !token.is_directive()) {
+            self.pos += 1;
+        }
+    }
+
+    fn run(&mut self) -> Result<(), SceneError> {
+        while let Some(token) = self.next() {
+            let TokenKind::Ident(directive) = token.kind.clone() else {
+                return Err(self.error(&token, "expected a directive".to_string()));
+            };
+            match directive.as_str() {
+                "Identity" => self.graphics.ctm = Transform::identity(),
+                "Translate" => {
+                    let v = self.expect_numbers(&token, 3)?;
+                    self.apply(Transform::translate(Vec3::new(v[0], v[1], v[2])));
+                }
+                "Scale" => {
+                    let v = self.expect_numbers(&token, 3)?;
+                    self.apply(Transform::scale(v[0], v[1], v[2]));
+                }
+                "Rotate" => {
+                    let v = self.expect_numbers(&token, 4)?;
+                    self.apply(Transform::rotate(v[0], Vec3::new(v[1], v[2], v[3])));
+                }
+                "LookAt" => {
+                    let v = self.expect_numbers(&token, 9)?;
+                    let camera_from_world = look_at(
+                        Point3::new(v[0], v[1], v[2]),
+                        Point3::new(v[3], v[4], v[5]),
+                        Vec3::new(v[6], v[7], v[8]),
+                    )
+                    .ok_or_else(|| self.error(&token, "degenerate LookAt".to_string()))?;
+                    self.apply(camera_from_world);
+                }
+                "Transform" | "ConcatTransform" => {
+                    let v = self.expect_numbers(&token, 16)?;
+                    // pbrt files store the matrix column major
+                    let mut m = [[0.0; 4]; 4];
+                    for (i, row) in m.iter_mut().enumerate() {
+                        for (j, value) in row.iter_mut().enumerate() {
+                            *value = v[j * 4 + i];
+                        }
+                    }
+                    let transform = Transform::from_matrix(m)
+                        .ok_or_else(|| self.error(&token, "singular matrix".to_string()))?;
+                    if directive == "Transform" {
+                        self.graphics.ctm = transform;
+                    } else {
+                        self.apply(transform);
+                    }
+                }
+                "CoordinateSystem" => {
+                    let (_, name) = self.expect_string(&token)?;
+                    self.named_coordinate_systems
+                        .insert(name, self.graphics.ctm);
+                }
+                "CoordSysTransform" => {
+                    let (name_token, name) = self.expect_string(&token)?;
+                    match self.named_coordinate_systems.get(&name) {
+                        Some(transform) => self.graphics.ctm = *transform,
+                        None => self.warn(
+                            &name_token,
+                            format!("unknown coordinate system `{}` ignored", name),
+                        ),
+                    }
+                }
+                "ReverseOrientation" => {
+                    self.graphics.reverse_orientation = !self.graphics.reverse_orientation
+                }
+                "AttributeBegin" => self.stack.push((self.graphics.clone(), true)),
+                "TransformBegin" => self.stack.push((self.graphics.clone(), false)),
+                "AttributeEnd" | "TransformEnd" => match self.stack.pop() {
+                    Some((graphics, true)) => self.graphics = graphics,
+                    Some((graphics, false)) => self.graphics.ctm = graphics.ctm,
+                    None => return Err(self.error(&token, format!("unmatched `{}`", directive))),
+                },
+                "ObjectBegin" => {
+                    let (_, name) = self.expect_string(&token)?;
+                    self.warn(
+                        &token,
+                        format!("object instancing is not supported, `{}` skipped", name),
+                    );
+                    self.stack.push((self.graphics.clone(), true));
+                    self.object_depth += 1;
+                }
+                "ObjectEnd" => {
+                    match self.stack.pop() {
+                        Some((graphics, _)) => self.graphics = graphics,
+                        None => return Err(self.error(&token, "unmatched `ObjectEnd`".to_string())),
+                    }
+                    self.object_depth = self.object_depth.saturating_sub(1);
+                }
+                "WorldBegin" => {
+                    self.graphics.ctm = Transform::identity();
+                    self.named_coordinate_systems
+                        .insert("world".to_string(), Transform::identity());
+                }
+                // pbrt-v3 only, the scene simply ends
+                "WorldEnd" => {}
+                "Camera" => self.camera_directive(&token)?,
+                "Film" => {
+                    let (_, ty) = self.expect_string(&token)?;
+                    let params = self.parse_params()?;
+                    let width = params.float("xresolution").unwrap_or(1280.0);
+                    let height = params.float("yresolution").unwrap_or(720.0);
+                    if width < 2.0 || height < 2.0 {
+                        return Err(self.error(
+                            &token,
+                            format!("film must be at least 2x2 pixels, got {}x{}", width, height),
+                        ));
+                    }
+                    self.resolution = (width as u32, height as u32);
+                    // where pbrt would write the image, the output is chosen on the command line
+                    params.find("filename");
+                    self.report_unused(&params, &format!("Film \"{}\"", ty));
+                }
+                "Sampler" => {
+                    let (_, ty) = self.expect_string(&token)?;
+                    let params = self.parse_params()?;
+                    if let Some(spp) = params.float("pixelsamples") {
+                        self.config.samples_per_pixel = spp.max(1.0) as u32;
+                    }
+                    self.report_unused(&params, &format!("Sampler \"{}\"", ty));
+                }
+                "Integrator" => {
+                    let (ty_token, ty) = self.expect_string(&token)?;
+                    if ty != "path" && ty != "volpath" {
+                        self.warn(
+                            &ty_token,
+                            format!("integrator `{}` is rendered with the path tracer", ty),
+                        );
+                    }
+                    let params = self.parse_params()?;
+                    if let Some(depth) = params.float("maxdepth") {
+                        self.config.max_depth = depth.max(1.0) as u32;
+                    }
+                    self.report_unused(&params, &format!("Integrator \"{}\"", ty));
+                }
+                "Material" => {
+                    let (ty_token, ty) = self.expect_string(&token)?;
+                    let params = self.parse_params()?;
+                    self.graphics.material = self.material(&ty_token, &ty, &params);
+                    self.report_unused(&params, &format!("Material \"{}\"", ty));
+                }
+                "MakeNamedMaterial" => {
+                    let (_, name) = self.expect_string(&token)?;
+                    let params = self.parse_params()?;
+                    let ty = params.string("type").unwrap_or_default();
+                    let material = self.material(&token, &ty, &params);
+                    self.named_materials.insert(name.clone(), material);
+                    self.report_unused(&params, &format!("MakeNamedMaterial \"{}\"", name));
+                }
+                "NamedMaterial" => {
+                    let (name_token, name) = self.expect_string(&token)?;
+                    match self.named_materials.get(&name) {
+                        Some(material) => self.graphics.material = Arc::clone(material),
+                        None => {
+                            return Err(self
+                                .error(&name_token, format!("unknown named material `{}`", name)))
+                        }
+                    }
+                }
+                "AreaLightSource" => {
+                    let (ty_token, ty) = self.expect_string(&token)?;
+                    let params = self.parse_params()?;
+                    if ty != "diffuse" {
+                        self.warn(
+                            &ty_token,
+                            format!("area light `{}` is treated as diffuse", ty),
+                        );
+                    }
+                    let l = self
+                        .color(&params, "L")
+                        .unwrap_or(Color::new(1.0, 1.0, 1.0));
+                    let scale = params.float("scale").unwrap_or(1.0);
+                    if params.bool("twosided") == Some(true) {
+                        self.warn(
+                            &ty_token,
+                            "two sided area lights emit from the front only".to_string(),
+                        );
+                    }
+                    self.graphics.area_light = Some(Arc::new(DiffuseLight::new(scale * l)));
+                    self.report_unused(&params, &format!("AreaLightSource \"{}\"", ty));
+                }
+                "LightSource" => {
+                    let (ty_token, ty) = self.expect_string(&token)?;
+                    let params = self.parse_params()?;
+                    if ty == "infinite" && params.ty("filename").is_none() {
+                        let l = self
+                            .color(&params, "L")
+                            .unwrap_or(Color::new(1.0, 1.0, 1.0));
+                        let scale = params.float("scale").unwrap_or(1.0);
+                        self.world.background = Background::Solid { color: scale * l };
+                        self.report_unused(&params, "LightSource \"infinite\"");
+                    } else {
+                        self.warn(
+                            &ty_token,
+                            format!("light source `{}` is not supported, skipped", ty),
+                        );
+                    }
+                }
+                "Shape" => self.shape_directive(&token)?,
+                "Include" | "Import" => {
+                    let (_, file) = self.expect_string(&token)?;
+                    let path = self.resolve(&token, &file);
+                    let source = fs::read_to_string(&path).map_err(|source| SceneError::Io {
+                        path: path.clone(),
+                        source,
+                    })?;
+                    let file_ix = self.files.len();
+                    self.files.push(path.clone());
+                    let tokens = tokenize(&source, file_ix).map_err(|(location, message)| {
+                        SceneError::Parse {
+                            path,
+                            location: Some(location),
+                            message,
+                        }
+                    })?;
+                    self.tokens.splice(self.pos..self.pos, tokens);
+                }
+                _ => {
+                    self.warn(
+                        &token,
+                        format!("unsupported directive `{}` ignored", directive),
+                    );
+                    self.skip_arguments();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn apply(&mut self, transform: Transform) {
+        self.graphics.ctm = self.graphics.ctm * transform;
+    }
+
+    fn resolve(&self, token: &Token, file: &str) -> PathBuf {
+        let including = &self.files[token.file];
+        including
+            .parent()
+            .map_or_else(|| PathBuf::from(file), |dir| dir.join(file))
+    }
+
+    fn camera_directive(&mut self, token: &Token) -> Result<(), SceneError> {
+        let (ty_token, ty) = self.expect_string(token)?;
+        let params = self.parse_params()?;
+        let orthographic = ty == "orthographic";
+        if ty != "perspective" && !orthographic {
+            self.warn(
+                &ty_token,
+                format!(
+                    "camera `{}` is not supported, using a perspective camera",
+                    ty
+                ),
+            );
+        }
+        // only the orthographic camera's screen window is supported
+        let screen_window = match orthographic {
+            true => match params.floats("screenwindow").as_deref() {
+                Some(&[x0, x1, y0, y1]) => Some([x0, x1, y0, y1]),
+                _ => None,
+            },
+            false => None,
+        };
+        let camera_from_world = self.graphics.ctm;
+        self.named_coordinate_systems
+            .insert("camera".to_string(), camera_from_world.inverse());
+        self.camera = Some(CameraState {
+            world_from_camera: camera_from_world.inverse(),
+            orthographic,
+            screen_window,
+            fov: match orthographic {
+                true => 90.0,
+                false => params.float("fov").unwrap_or(90.0),
+            },
+            lens_radius: params.float("lensradius").unwrap_or(0.0),
+            focal_distance: params.float("focaldistance").unwrap_or(1.0e6),
+        });
+        self.report_unused(&params, &format!("Camera \"{}\"", ty));
+        Ok(())
+    }
+
+    fn material(&mut self, token: &Token, ty: &str, params: &ParamSet) -> Arc<dyn Scatter> {
+        match ty {
+            "diffuse" | "matte" => {
+                let name = if ty == "diffuse" { "reflectance" } else { "Kd" };
+                let albedo = self
+                    .color(params, name)
+                    .unwrap_or(Color::new(0.5, 0.5, 0.5));
+                Arc::new(Lambertian::new(albedo))
+            }
+            "conductor" | "metal" | "mirror" => {
+                let name = if ty == "mirror" { "Kr" } else { "reflectance" };
+                let albedo = self
+                    .color(params, name)
+                    .unwrap_or(Color::new(0.9, 0.9, 0.9));
+                let fuzz = if ty == "mirror" {
+                    0.0
+                } else {
+                    params
+                        .float("roughness")
+                        .or_else(|| params.float("uroughness"))
+                        .unwrap_or(if ty == "metal" { 0.01 } else { 0.0 })
+                };
+                if params.ty("eta").is_some() || params.ty("k").is_some() {
+                    params.find("eta");
+                    params.find("k");
+                    self.warn(
+                        token,
+                        "conductor eta and k are approximated by a grey reflectance".to_string(),
+                    );
+                }
+                params.find("vroughness");
+                params.find("remaproughness");
+                Arc::new(Metal::new(albedo, fuzz.clamp(0.0, 1.0)))
+            }
+            "dielectric" | "glass" | "thindielectric" => {
+                let ior = params
+                    .float("eta")
+                    .or_else(|| params.float("index"))
+                    .unwrap_or(1.5);
+                Arc::new(Dielectric::new(ior))
+            }
+            "coateddiffuse" | "plastic" | "substrate" | "uber" => {
+                let name = if ty == "coateddiffuse" {
+                    "reflectance"
+                } else {
+                    "Kd"
+                };
+                let albedo = self
+                    .color(params, name)
+                    .unwrap_or(Color::new(0.5, 0.5, 0.5));
+                self.warn(
+                    token,
+                    format!("material `{}` is approximated as diffuse", ty),
+                );
+                // the coating has no counterpart, don't report each of its parameters
+                for param in &params.params {
+                    param.used.set(true);
+                }
+                Arc::new(Lambertian::new(albedo))
+            }
+            _ => {
+                self.warn(
+                    token,
+                    format!("material `{}` is not supported, using a grey diffuse", ty),
+                );
+                for param in &params.params {
+                    param.used.set(true);
+                }
+                default_material()
+            }
+        }
+    }
+
+    fn shape_directive(&mut self, token: &Token) -> Result<(), SceneError> {
+        let (ty_token, ty) = self.expect_string(token)?;
+        let params = self.parse_params()?;
+        if self.object_depth > 0 {
+            return Ok(());
+        }
+        let material = self
+            .graphics
+            .area_light
+            .clone()
+            .unwrap_or_else(|| Arc::clone(&self.graphics.material));
+        let to_world = mirror() * self.graphics.ctm;
+        self.shape_count += 1;
+        let name = format!("{}_{:04}", ty, self.shape_count);
+
+        match ty.as_str() {
+            "sphere" => {
+                let radius = params.float("radius").unwrap_or(1.0);
+                for partial in ["zmin", "zmax", "phimax"] {
+                    if params.ty(partial).is_some() {
+                        params.find(partial);
+                        self.warn(
+                            &ty_token,
+                            format!("`{}` ignored, rendering a full sphere", partial),
+                        );
+                    }
+                }
+                let scales = [
+                    Vec3::new(1.0, 0.0, 0.0),
+                    Vec3::new(0.0, 1.0, 0.0),
+                    Vec3::new(0.0, 0.0, 1.0),
+                ]
+                .map(|axis| to_world.vector(axis).length());
+                let scale = scales.iter().sum::<f64>() / 3.0;
+                if scales.iter().any(|s| (s - scale).abs() > 1.0e-6 * scale) {
+                    self.warn(
+                        &ty_token,
+                        "non-uniformly scaled sphere approximated by a sphere".to_string(),
+                    );
+                }
+                let center = to_world.point(Point3::empty());
+                self.world.add(
+                    name,
+                    Box::new(Sphere::new(center, radius * scale, material)),
+                );
+            }
+            "trianglemesh" => {
+                let positions = params.floats("P").unwrap_or_default();
+                let indices = match params.floats("indices") {
+                    Some(indices) => indices,
+                    None if positions.len() == 9 => vec![0.0, 1.0, 2.0],
+                    None => {
+                        return Err(
+                            self.error(&ty_token, "trianglemesh without indices".to_string())
+                        )
+                    }
+                };
+                let normals = params.floats("N");
+                let uvs = params.floats("uv").or_else(|| params.floats("st"));
+                let mesh = MeshData {
+                    positions: triples(&positions)
+                        .map(|[x, y, z]| Point3::new(x, y, z))
+                        .collect(),
+                    normals: normals
+                        .filter(|normals| !normals.is_empty())
+                        .map(|normals| {
+                            triples(&normals)
+                                .map(|[x, y, z]| Vec3::new(x, y, z))
+                                .collect()
+                        }),
+                    uvs: uvs
+                        .filter(|uvs| !uvs.is_empty())
+                        .map(|uvs| uvs.chunks_exact(2).map(|c| (c[0], c[1])).collect()),
+                    colors: None,
+                    indices: triples(&indices)
+                        .map(|triangle| triangle.map(|ix| ix as usize))
+                        .collect(),
+                };
+                mesh.validate()
+                    .map_err(|message| self.error(&ty_token, message))?;
+                self.add_mesh(name, mesh, &to_world, material);
+            }
+            "plymesh" => {
+                let Some(file) = params.string("filename") else {
+                    return Err(self.error(&ty_token, "plymesh without filename".to_string()));
+                };
+                let path = self.resolve(token, &file);
+                match load_mesh(&path) {
+                    Ok(mesh) => self.add_mesh(name, mesh, &to_world, material),
+                    Err(MeshError::UnsupportedFormat { .. }) => self.warn(
+                        &ty_token,
+                        format!("mesh `{}` has an unsupported format, skipped", file),
+                    ),
+                    Err(err) => return Err(self.error(&ty_token, err.to_string())),
+                }
+            }
+            _ => {
+                self.warn(
+                    &ty_token,
+                    format!("shape `{}` is not supported, skipped", ty),
+                );
+                return Ok(());
+            }
+        }
+        self.report_unused(&params, &format!("Shape \"{}\"", ty));
+        Ok(())
+    }
+
+    fn add_mesh(
+        &mut self,
+        name: String,
+        mesh: MeshData,
+        to_world: &Transform,
+        mat: Arc<dyn Scatter>,
+    ) {
+        let mut mesh = mesh.transformed(to_world);
+        if self.graphics.reverse_orientation {
+            for triangle in &mut mesh.indices {
+                triangle.swap(1, 2);
+            }
+        }
+        self.world.add(name, Box::new(TriangleMesh::new(mesh, mat)));
+    }
+
+    fn finish(self) -> Result<PbrtImport, SceneError> {
+        if let (Some(_), Some(token)) = (self.stack.last(), self.tokens.last()) {
+            return Err(self.error(token, "missing `AttributeEnd`".to_string()));
+        }
+        let mut config = self.config;
+        let (width, height) = self.resolution;
+        config.image_width = width;
+        config.image_height = height;
+        config.aspect_ratio = width as f64 / height as f64;
+
+        let camera = self.camera.unwrap_or(CameraState {
+            world_from_camera: Transform::identity(),
+            orthographic: false,
+            screen_window: None,
+            fov: 90.0,
+            lens_radius: 0.0,
+            focal_distance: 1.0e6,
+        });
+        // pbrt's camera looks down +z with +y up
+        let to_world = mirror() * camera.world_from_camera;
+        config.lookfrom = to_world.point(Point3::empty());
+        config.lookat = to_world.point(Point3::new(0.0, 0.0, 1.0));
+        config.vup = to_world.vector(Vec3::new(0.0, 1.0, 0.0));
+        // pbrt's fov spans the shorter image axis
+        config.vfov = if width >= height {
+            camera.fov
+        } else {
+            let half = (camera.fov / 2.0).to_radians().tan() / config.aspect_ratio;
+            2.0 * half.atan().to_degrees()
+        };
+        if camera.orthographic {
+            // the default screen window spans [-1, 1] on the shorter axis
+            let view_height = match camera.screen_window {
+                Some([_, _, y0, y1]) => y1 - y0,
+                None if width >= height => 2.0,
+                None => 2.0 / config.aspect_ratio,
+            };
+            config.projection = Projection::Orthographic { view_height };
+        }
+        config.aperature = 2.0 * camera.lens_radius;
+        config.dist_to_focus = if camera.lens_radius > 0.0 {
+            camera.focal_distance
+        } else {
+            (config.lookat - config.lookfrom).length()
+        };
+
+        Ok(PbrtImport {
+            scene: Scene {
+                world: self.world,
+                config,
+            },
+            warnings: self.warnings,
+        })
+    }
+}
+
+fn triples(values: &[f64]) -> impl Iterator<Item = [f64; 3]> + '_ {
+    values.chunks_exact(3).map(|c| [c[0], c[1], c[2]])
+}
+
+// Camera from world transform of pbrt's `LookAt`
+fn look_at(eye: Point3, look: Point3, up: Vec3) -> Option<Transform> {
+    let dir = (look - eye).unit_vector();
+    let right = up.unit_vector().cross(&dir);
+    if right.length() == 0.0 || !right.length().is_finite() {
+        return None;
+    }
+    let right = right.unit_vector();
+    let new_up = dir.cross(&right);
+    let row = |axis| {
+        [
+            right.axis(axis),
+            new_up.axis(axis),
+            dir.axis(axis),
+            eye.axis(axis),
+        ]
+    };
+    let m = [row(0), row(1), row(2), [0.0, 0.0, 0.0, 1.0]];
+    Some(Transform::from_matrix(m)?.inverse())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(source: &str) -> Vec<TokenKind> {
+        let tokens = tokenize(source, 0).unwrap();
+        tokens.into_iter().map(|token| token.kind).collect()
+    }
+
+    fn parse_params(source: &str) -> (Importer, Result<ParamSet, SceneError>) {
+        let mut importer = Importer::new(Path::new("test.pbrt"));
+        importer.tokens = tokenize(source, 0).unwrap();
+        let params = importer.parse_params();
+        (importer, params)
+    }
+
+    fn ident(word: &str) -> TokenKind {
+        TokenKind::Ident(word.to_string())
+    }
+
+    fn string(value: &str) -> TokenKind {
+        TokenKind::Str(value.to_string())
+    }
+
+    #[test]
+    fn tokenizes_directives_strings_numbers_and_brackets() {
+        let source = "Shape \"sphere\" \"float radius\" [ 2.5 ] # a comment\nWorldEnd";
+        assert_eq!(
+            kinds(source),
+            vec![
+                ident("Shape"),
+                string("sphere"),
+                string("float radius"),
+                TokenKind::Open,
+                TokenKind::Num(2.5),
+                TokenKind::Close,
+                ident("WorldEnd"),
+            ]
+        );
+    }
+
+    #[test]
+    fn numbers_end_at_brackets() {
+        assert_eq!(
+            kinds("[1 -2.5e-1]"),
+            vec![
+                TokenKind::Open,
+                TokenKind::Num(1.0),
+                TokenKind::Num(-0.25),
+                TokenKind::Close,
+            ]
+        );
+    }
+
+    #[test]
+    fn unescapes_strings() {
+        assert_eq!(kinds(r#""a\"b\n\tc""#), vec![string("a\"b\n\tc")]);
+    }
+
+    #[test]
+    fn locates_tokens() {
+        let tokens = tokenize("Translate 1 2 3\n  Scale 2 2 2", 0).unwrap();
+        assert_eq!(tokens[4].kind, ident("Scale"));
+        assert_eq!(tokens[4].location, SourceLocation { line: 2, column: 3 });
+    }
+
+    #[test]
+    fn rejects_unterminated_strings() {
+        let (location, message) = tokenize("Shape \"sphere\nWorldEnd", 0).unwrap_err();
+        assert_eq!(location, SourceLocation { line: 1, column: 7 });
+        assert_eq!(message, "unterminated string");
+    }
+
+    #[test]
+    fn parses_bracketed_and_bare_values() {
+        let source =
+            r#""float radius" 2 "rgb Kd" [0.1 0.2 0.3] "bool flip" true "string filename" "a.ply""#;
+        let (_, params) = parse_params(source);
+        let params = params.unwrap();
+        assert_eq!(params.float("radius"), Some(2.0));
+        assert_eq!(params.ty("Kd"), Some("rgb"));
+        assert_eq!(params.floats("Kd"), Some(vec![0.1, 0.2, 0.3]));
+        assert_eq!(params.bool("flip"), Some(true));
+        assert_eq!(params.string("filename"), Some("a.ply".to_string()));
+        assert_eq!(params.float("filename"), None);
+        assert_eq!(params.float("missing"), None);
+    }
+
+    #[test]
+    fn reports_parameters_never_looked_up() {
+        let (_, params) = parse_params(r#""float radius" 1 "float zmin" -1 "float zmax" 1"#);
+        let params = params.unwrap();
+        params.float("radius");
+        let unused: Vec<&str> = params.unused().map(|param| param.name.as_str()).collect();
+        assert_eq!(unused, vec!["zmin", "zmax"]);
+    }
+
+    #[test]
+    fn parameters_end_at_the_next_directive() {
+        let (mut importer, params) = parse_params(r#""float radius" 1 Shape "sphere""#);
+        assert_eq!(params.unwrap().params.len(), 1);
+        assert_eq!(
+            importer.next().map(|token| token.kind),
+            Some(ident("Shape"))
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_parameters() {
+        let (_, params) = parse_params(r#""radius" 1"#);
+        assert!(matches!(params, Err(SceneError::Parse { message, .. })
+            if message.contains("expected a `\"type name\"` parameter")));
+
+        let (_, params) = parse_params(r#""float radius" [ 1"#);
+        assert!(matches!(params, Err(SceneError::Parse { message, .. })
+            if message == "missing value for `radius`"));
+
+        let (_, params) = parse_params(r#""float radius" ["#);
+        assert!(params.is_err());
+    }
+
+    // pbrt looks down -z at a sphere on its +x side, which is left in the
+    // image as pbrt's world is left handed
+    const SCENE: &str = r#"
+LookAt 0 0 5  0 0 0  0 1 0
+Camera "perspective" "float fov" 45
+Film "rgb" "integer xresolution" 200 "integer yresolution" 100
+WorldBegin
+Shape "trianglemesh" "point3 P" [ -1 -1 0  1 -1 0  0 1 0 ] "integer indices" [ 0 1 2 ]
+AttributeBegin
+  Translate 0 3 0
+  ReverseOrientation
+  Shape "trianglemesh" "point3 P" [ -1 -1 0  1 -1 0  0 1 0 ] "integer indices" [ 0 1 2 ]
+AttributeEnd
+AttributeBegin
+  Translate 2 0 0
+  Shape "sphere" "float radius" 0.5
+AttributeEnd
+"#;
+
+    #[test]
+    fn imports_the_camera_mirrored() {
+        let import = parse_pbrt(SCENE, Path::new("test.pbrt")).unwrap();
+        assert!(import.warnings.is_empty());
+        let config = import.scene.config;
+        assert_eq!((config.image_width, config.image_height), (200, 100));
+        assert_eq!(config.lookfrom, Point3::new(0.0, 0.0, 5.0));
+        let view = (config.lookat - config.lookfrom).unit_vector();
+        assert_eq!(view, Vec3::new(0.0, 0.0, -1.0));
+        assert_eq!(config.vup, Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(config.vfov, 45.0);
+
+        let world = &import.scene.world;
+        assert_eq!(
+            world.world_origin("sphere_0003"),
+            Some(Point3::new(-2.0, 0.0, 0.0))
+        );
+        // the sphere's center is 0.4 / tan(22.5°) half heights left of the middle
+        let pick = crate::pick(world, &config, 52, 50).unwrap();
+        assert_eq!(pick.name, "sphere_0003");
+        assert!(crate::pick(world, &config, 148, 50).is_none());
+    }
+
+    #[test]
+    fn keeps_triangles_facing_their_pbrt_normal() {
+        let import = parse_pbrt(SCENE, Path::new("test.pbrt")).unwrap();
+        let world = &import.scene.world;
+        let front_face = |y: f64| {
+            let ray = crate::Ray::new(Point3::new(0.0, y, 5.0), Vec3::new(0.0, 0.0, -1.0));
+            let hit = crate::Hittable::hit(world, &ray, (0.001, f64::INFINITY)).unwrap();
+            hit.front_face
+        };
+        // pbrt's normal of the triangle points along +z, at the camera
+        assert!(front_face(0.0));
+        assert!(!front_face(3.0));
+    }
+}
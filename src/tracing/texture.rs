@@ -0,0 +1,61 @@
+use image::DynamicImage;
+
+use crate::{srgb_to_linear, Color};
+
+/// Bilinearly filtered image, repeating outside of `[0, 1]`.
+#[derive(Debug, Clone)]
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    // linear, top row first
+    pixels: Vec<Color>,
+}
+
+impl ImageTexture {
+    /// `srgb` textures (colors as opposed to data such as roughness) are
+    /// converted to linear values.
+    pub fn from_image(image: &DynamicImage, srgb: bool) -> ImageTexture {
+        let rgb = image.to_rgb32f();
+        let decode = |c: f32| {
+            if srgb {
+                srgb_to_linear(c as f64)
+            } else {
+                c as f64
+            }
+        };
+        ImageTexture {
+            width: rgb.width() as usize,
+            height: rgb.height() as usize,
+            pixels: rgb
+                .pixels()
+                .map(|p| Color::new(decode(p[0]), decode(p[1]), decode(p[2])))
+                .collect(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// `v` points up, `(0, 0)` is the bottom left corner.
+    pub fn sample(&self, u: f64, v: f64) -> Color {
+        if self.pixels.is_empty() {
+            return Color::new(1.0, 1.0, 1.0);
+        }
+        let x = u.rem_euclid(1.0) * self.width as f64 - 0.5;
+        let y = (1.0 - v.rem_euclid(1.0)) * self.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let texel = |x: f64, y: f64| {
+            let x = (x as i64).rem_euclid(self.width as i64) as usize;
+            let y = (y as i64).rem_euclid(self.height as i64) as usize;
+            self.pixels[y * self.width + x]
+        };
+        (1.0 - fy) * ((1.0 - fx) * texel(x0, y0) + fx * texel(x0 + 1.0, y0))
+            + fy * ((1.0 - fx) * texel(x0, y0 + 1.0) + fx * texel(x0 + 1.0, y0 + 1.0))
+    }
+}
@@ -0,0 +1,215 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{camera, Color};
+
+/// Curve used to compress scene linear radiance into the displayable `[0, 1]` range.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToneMapOperator {
+    /// Hard clamp, anything above 1.0 is blown out.
+    Clamp,
+    Reinhard,
+    /// Reinhard with a white point, values at `white_point` map to 1.0.
+    ExtendedReinhard {
+        white_point: f64,
+    },
+    /// Narkowicz fit of the ACES filmic reference curve.
+    AcesFilmic,
+    /// John Hable's Uncharted 2 filmic curve.
+    Hable,
+}
+
+/// Encoding applied after tone mapping, right before quantizing.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferFunction {
+    Linear,
+    Gamma(f64),
+    Srgb,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToneMapping {
+    /// Exposure in stops (EV), radiance is scaled by `2^exposure`.
+    pub exposure: f64,
+    pub operator: ToneMapOperator,
+    pub transfer: TransferFunction,
+}
+
+impl ToneMapping {
+    pub fn new(
+        exposure: f64,
+        operator: ToneMapOperator,
+        transfer: TransferFunction,
+    ) -> ToneMapping {
+        ToneMapping {
+            exposure,
+            operator,
+            transfer,
+        }
+    }
+
+    /// `true` unless a white point or gamma of zero or less would turn the
+    /// image into NaNs.
+    pub fn is_valid(&self) -> bool {
+        let white_point = match self.operator {
+            ToneMapOperator::ExtendedReinhard { white_point } => white_point,
+            _ => 1.0,
+        };
+        let gamma = match self.transfer {
+            TransferFunction::Gamma(gamma) => gamma,
+            _ => 1.0,
+        };
+        white_point > 0.0 && gamma > 0.0
+    }
+
+    /// Maps linear radiance to display encoded values in `[0, 1]`.
+    pub fn apply(&self, color: Color) -> Color {
+        let exposed = color * 2.0_f64.powf(self.exposure);
+        let mapped = Color::new(
+            self.operator.map(exposed.x()),
+            self.operator.map(exposed.y()),
+            self.operator.map(exposed.z()),
+        );
+        Color::new(
+            self.transfer.encode(mapped.x()),
+            self.transfer.encode(mapped.y()),
+            self.transfer.encode(mapped.z()),
+        )
+    }
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        ToneMapping::new(0.0, ToneMapOperator::Clamp, TransferFunction::Srgb)
+    }
+}
+
+impl ToneMapOperator {
+    pub fn map(&self, x: f64) -> f64 {
+        let x = x.max(0.0);
+        let mapped = match *self {
+            ToneMapOperator::Clamp => x,
+            ToneMapOperator::Reinhard => x / (1.0 + x),
+            ToneMapOperator::ExtendedReinhard { white_point } => {
+                x * (1.0 + x / (white_point * white_point)) / (1.0 + x)
+            }
+            ToneMapOperator::AcesFilmic => {
+                let x = 0.6 * x;
+                (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)
+            }
+            ToneMapOperator::Hable => {
+                const EXPOSURE_BIAS: f64 = 2.0;
+                const WHITE_POINT: f64 = 11.2;
+                hable_partial(EXPOSURE_BIAS * x) / hable_partial(WHITE_POINT)
+            }
+        };
+        camera::clamp(mapped, 0.0, 1.0)
+    }
+}
+
+fn hable_partial(x: f64) -> f64 {
+    const A: f64 = 0.15;
+    const B: f64 = 0.50;
+    const C: f64 = 0.10;
+    const D: f64 = 0.20;
+    const E: f64 = 0.02;
+    const F: f64 = 0.30;
+    ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F
+}
+
+impl TransferFunction {
+    pub fn encode(&self, x: f64) -> f64 {
+        match *self {
+            TransferFunction::Linear => x,
+            TransferFunction::Gamma(gamma) => x.powf(1.0 / gamma),
+            TransferFunction::Srgb => {
+                if x <= 0.0031308 {
+                    12.92 * x
+                } else {
+                    1.055 * x.powf(1.0 / 2.4) - 0.055
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPERATORS: [ToneMapOperator; 5] = [
+        ToneMapOperator::Clamp,
+        ToneMapOperator::Reinhard,
+        ToneMapOperator::ExtendedReinhard { white_point: 4.0 },
+        ToneMapOperator::AcesFilmic,
+        ToneMapOperator::Hable,
+    ];
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn operators_map_black_to_black_and_stay_in_range() {
+        for operator in OPERATORS {
+            assert_close(operator.map(0.0), 0.0);
+            assert_close(operator.map(-1.0), 0.0);
+            let mut previous = 0.0;
+            for i in 1..=1000 {
+                let mapped = operator.map(i as f64 * 0.1);
+                assert!((0.0..=1.0).contains(&mapped), "{:?}", operator);
+                assert!(mapped >= previous, "{:?} isn't monotonic", operator);
+                previous = mapped;
+            }
+        }
+    }
+
+    #[test]
+    fn operators_hit_their_white_points() {
+        assert_close(ToneMapOperator::Clamp.map(1.0), 1.0);
+        assert_close(ToneMapOperator::Clamp.map(2.0), 1.0);
+        assert_close(ToneMapOperator::Reinhard.map(1.0), 0.5);
+        assert_close(
+            ToneMapOperator::ExtendedReinhard { white_point: 4.0 }.map(4.0),
+            1.0,
+        );
+        // the Hable curve is normalised by its white point of 11.2 after the 2x bias
+        assert_close(ToneMapOperator::Hable.map(5.6), 1.0);
+        assert_close(ToneMapOperator::AcesFilmic.map(100.0), 1.0);
+    }
+
+    #[test]
+    fn transfers_keep_the_endpoints() {
+        for transfer in [
+            TransferFunction::Linear,
+            TransferFunction::Gamma(2.2),
+            TransferFunction::Srgb,
+        ] {
+            assert_close(transfer.encode(0.0), 0.0);
+            assert!((transfer.encode(1.0) - 1.0).abs() < 1e-6, "{:?}", transfer);
+        }
+        assert_close(TransferFunction::Gamma(2.0).encode(0.25), 0.5);
+        // the linear toe of the sRGB curve
+        assert_close(TransferFunction::Srgb.encode(0.001), 0.01292);
+    }
+
+    #[test]
+    fn exposure_is_in_stops() {
+        let tone_mapping = ToneMapping::new(1.0, ToneMapOperator::Clamp, TransferFunction::Linear);
+        let color = tone_mapping.apply(Color::new(0.25, 0.125, 1.0));
+        assert_close(color.x(), 0.5);
+        assert_close(color.y(), 0.25);
+        assert_close(color.z(), 1.0);
+    }
+
+    #[test]
+    fn rejects_non_positive_white_points_and_gammas() {
+        assert!(ToneMapping::default().is_valid());
+        let white_point = ToneMapOperator::ExtendedReinhard { white_point: 0.0 };
+        assert!(!ToneMapping::new(0.0, white_point, TransferFunction::Srgb).is_valid());
+        let gamma = TransferFunction::Gamma(-2.2);
+        assert!(!ToneMapping::new(0.0, ToneMapOperator::Clamp, gamma).is_valid());
+    }
+}
@@ -0,0 +1,187 @@
+use std::ops::Mul;
+
+use crate::{Point3, Vec3};
+
+/// Affine transform stored as a row major 4x4 matrix together with its inverse.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Transform {
+    m: [[f64; 4]; 4],
+    m_inv: [[f64; 4]; 4],
+}
+
+const IDENTITY: [[f64; 4]; 4] = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform {
+            m: IDENTITY,
+            m_inv: IDENTITY,
+        }
+    }
+    /// `None` if the matrix is singular.
+    pub fn from_matrix(m: [[f64; 4]; 4]) -> Option<Transform> {
+        Some(Transform {
+            m,
+            m_inv: invert(&m)?,
+        })
+    }
+    pub fn translate(delta: Vec3) -> Transform {
+        let mut m = IDENTITY;
+        let mut m_inv = IDENTITY;
+        for axis in 0..3 {
+            m[axis][3] = delta.axis(axis);
+            m_inv[axis][3] = -delta.axis(axis);
+        }
+        Transform { m, m_inv }
+    }
+    pub fn scale(x: f64, y: f64, z: f64) -> Transform {
+        let mut m = IDENTITY;
+        let mut m_inv = IDENTITY;
+        for (axis, s) in [x, y, z].into_iter().enumerate() {
+            m[axis][axis] = s;
+            m_inv[axis][axis] = 1.0 / s;
+        }
+        Transform { m, m_inv }
+    }
+    /// Rotation by `degrees` around `axis`, counter clockwise looking down the axis.
+    pub fn rotate(degrees: f64, axis: Vec3) -> Transform {
+        let a = axis.unit_vector();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let mut m = IDENTITY;
+        m[0][0] = a.x() * a.x() + (1.0 - a.x() * a.x()) * cos;
+        m[0][1] = a.x() * a.y() * (1.0 - cos) - a.z() * sin;
+        m[0][2] = a.x() * a.z() * (1.0 - cos) + a.y() * sin;
+        m[1][0] = a.x() * a.y() * (1.0 - cos) + a.z() * sin;
+        m[1][1] = a.y() * a.y() + (1.0 - a.y() * a.y()) * cos;
+        m[1][2] = a.y() * a.z() * (1.0 - cos) - a.x() * sin;
+        m[2][0] = a.x() * a.z() * (1.0 - cos) - a.y() * sin;
+        m[2][1] = a.y() * a.z() * (1.0 - cos) + a.x() * sin;
+        m[2][2] = a.z() * a.z() + (1.0 - a.z() * a.z()) * cos;
+        // orthonormal, the inverse is the transpose
+        Transform {
+            m,
+            m_inv: transpose(&m),
+        }
+    }
+    pub fn inverse(&self) -> Transform {
+        Transform {
+            m: self.m_inv,
+            m_inv: self.m,
+        }
+    }
+    pub fn matrix(&self) -> &[[f64; 4]; 4] {
+        &self.m
+    }
+    pub fn is_identity(&self) -> bool {
+        self.m == IDENTITY
+    }
+    pub fn point(&self, p: Point3) -> Point3 {
+        let m = &self.m;
+        let x = m[0][0] * p.x() + m[0][1] * p.y() + m[0][2] * p.z() + m[0][3];
+        let y = m[1][0] * p.x() + m[1][1] * p.y() + m[1][2] * p.z() + m[1][3];
+        let z = m[2][0] * p.x() + m[2][1] * p.y() + m[2][2] * p.z() + m[2][3];
+        let w = m[3][0] * p.x() + m[3][1] * p.y() + m[3][2] * p.z() + m[3][3];
+        if w == 1.0 {
+            Point3::new(x, y, z)
+        } else {
+            Point3::new(x, y, z) / w
+        }
+    }
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * v.x() + m[0][1] * v.y() + m[0][2] * v.z(),
+            m[1][0] * v.x() + m[1][1] * v.y() + m[1][2] * v.z(),
+            m[2][0] * v.x() + m[2][1] * v.y() + m[2][2] * v.z(),
+        )
+    }
+    /// Normals transform by the inverse transpose.
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        let m = &self.m_inv;
+        Vec3::new(
+            m[0][0] * n.x() + m[1][0] * n.y() + m[2][0] * n.z(),
+            m[0][1] * n.x() + m[1][1] * n.y() + m[2][1] * n.z(),
+            m[0][2] * n.x() + m[1][2] * n.y() + m[2][2] * n.z(),
+        )
+    }
+    /// Whether the transform mirrors space, which flips triangle winding.
+    pub fn swaps_handedness(&self) -> bool {
+        let m = &self.m;
+        let det = m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+        det < 0.0
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::identity()
+    }
+}
+
+// Composition, `(a * b).point(p) == a.point(b.point(p))`
+impl Mul for Transform {
+    type Output = Transform;
+
+    fn mul(self, rhs: Transform) -> Self::Output {
+        Transform {
+            m: mat_mul(&self.m, &rhs.m),
+            m_inv: mat_mul(&rhs.m_inv, &self.m_inv),
+        }
+    }
+}
+
+fn mat_mul(a: &[[f64; 4]; 4], b: &[[f64; 4]; 4]) -> [[f64; 4]; 4] {
+    let mut r = [[0.0; 4]; 4];
+    for (i, row) in r.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..4).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    r
+}
+
+fn transpose(m: &[[f64; 4]; 4]) -> [[f64; 4]; 4] {
+    let mut r = [[0.0; 4]; 4];
+    for (i, row) in r.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = m[j][i];
+        }
+    }
+    r
+}
+
+// Gauss-Jordan elimination with partial pivoting
+fn invert(m: &[[f64; 4]; 4]) -> Option<[[f64; 4]; 4]> {
+    let mut a = *m;
+    let mut inv = IDENTITY;
+    for col in 0..4 {
+        let pivot = (col..4).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1.0e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        inv.swap(col, pivot);
+        let scale = 1.0 / a[col][col];
+        for j in 0..4 {
+            a[col][j] *= scale;
+            inv[col][j] *= scale;
+        }
+        for row in 0..4 {
+            if row != col {
+                let factor = a[row][col];
+                for j in 0..4 {
+                    a[row][j] -= factor * a[col][j];
+                    inv[row][j] -= factor * inv[col][j];
+                }
+            }
+        }
+    }
+    Some(inv)
+}
@@ -4,6 +4,7 @@ use std::{
 };
 
 use rand::Rng;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Vec3 {
@@ -15,7 +16,7 @@ impl Vec3 {
         Vec3 { e: [e0, e1, e2] }
     }
     pub fn random(r: Range<f64>) -> Vec3 {
-        let mut rng = rand::thread_rng();
+        let mut rng = crate::rng();
         Vec3::new(
             rng.gen_range(r.clone()),
             rng.gen_range(r.clone()),
@@ -43,7 +44,7 @@ impl Vec3 {
         }
     }
     pub fn random_in_unit_disk() -> Vec3 {
-        let mut rng = rand::thread_rng();
+        let mut rng = crate::rng();
         loop {
             let p = Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), 0.0);
             if p.length() < 1.0 {
@@ -95,6 +96,23 @@ impl Vec3 {
     pub fn z(&self) -> f64 {
         self.e[2]
     }
+    pub fn axis(&self, axis: usize) -> f64 {
+        self.e[axis]
+    }
+    pub fn min(&self, rhs: &Vec3) -> Vec3 {
+        Vec3::new(
+            self.x().min(rhs.x()),
+            self.y().min(rhs.y()),
+            self.z().min(rhs.z()),
+        )
+    }
+    pub fn max(&self, rhs: &Vec3) -> Vec3 {
+        Vec3::new(
+            self.x().max(rhs.x()),
+            self.y().max(rhs.y()),
+            self.z().max(rhs.z()),
+        )
+    }
 }
 
 // Neg
@@ -275,4 +293,18 @@ impl Display for Vec3 {
     }
 }
 
+// Serialized as a plain `[x, y, z]` array
+impl Serialize for Vec3 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.e.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Vec3 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let e = <[f64; 3]>::deserialize(deserializer)?;
+        Ok(Vec3 { e })
+    }
+}
+
 pub type Point3 = Vec3;